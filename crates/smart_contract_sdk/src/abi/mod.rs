@@ -1,3 +1,4 @@
+#[cfg(test)]
 mod tests;
 
 use sha3::{Digest, Keccak256};
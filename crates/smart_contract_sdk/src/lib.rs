@@ -1,4 +1,6 @@
+#[allow(dead_code)]
 mod abi;
+#[allow(dead_code)]
 mod constants;
 
 extern crate proc_macro;
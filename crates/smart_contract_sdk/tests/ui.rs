@@ -1,5 +1,5 @@
 #[test]
 fn ui() {
     let t = trybuild::TestCases::new();
-    t.pass("tests/01.rs");
+    t.pass("tests/ui/01.rs");
 }
@@ -12,5 +12,14 @@ pub enum ElfError {
     UnsupportedFormat(u8, u8),
 
     #[error("segment at paddr=0x{0:x} (memsz={1}) out of bounds")]
-    SegmentOutOfBounds(u32, usize),
+    SegmentOutOfBounds(u64, usize),
+
+    #[error("entry point 0x{0:x} is outside the 32-bit address space")]
+    EntryOutOfRange(u64),
+
+    #[error("no {0} section")]
+    MissingSection(&'static str),
+
+    #[error("section at 0x{0:x} is outside the 32-bit address space")]
+    SectionOutOfRange(u64),
 }
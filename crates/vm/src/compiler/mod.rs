@@ -4,12 +4,23 @@ use goblin::elf::{Elf, program_header::PT_LOAD};
 
 use crate::memory::Memory;
 
-mod errors;
+pub mod errors;
+#[cfg(test)]
 mod tests;
 
 #[derive(Debug)]
 pub struct LoadResult {
     pub entry: u32,
+    /// Whether the image is ELFCLASS64 and should run on [`CPU64`](crate::cpu64::CPU64).
+    pub is_64: bool,
+}
+
+/// The contents of an ELF's `.text` section and the address it runs at.
+#[derive(Debug)]
+pub struct TextSection {
+    pub addr: u32,
+    pub bytes: Vec<u8>,
+    pub is_64: bool,
 }
 
 pub struct ElfLoader;
@@ -19,19 +30,7 @@ impl ElfLoader {
         path: P,
         memory: &mut Memory,
     ) -> Result<LoadResult, errors::ElfError> {
-        let mut file = File::open(path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-
-        use goblin::elf::header::{EI_CLASS, EI_DATA, ELFCLASS32, ELFDATA2LSB};
-        if buffer.len() > EI_DATA as usize {
-            let class = buffer[EI_CLASS as usize];
-            let data = buffer[EI_DATA as usize];
-            if class != ELFCLASS32 || data != ELFDATA2LSB {
-                return Err(errors::ElfError::UnsupportedFormat(class, data));
-            }
-        }
-
+        let buffer = read_elf(path)?;
         let elf = Elf::parse(&buffer)?;
 
         for ph in &elf.program_headers {
@@ -39,13 +38,13 @@ impl ElfLoader {
                 continue;
             }
 
-            let paddr = ph.p_paddr as u32;
             let filesz = ph.p_filesz as usize;
             let memsz = ph.p_memsz as usize;
 
-            if (paddr as usize).saturating_add(memsz) > memory.size() {
-                return Err(errors::ElfError::SegmentOutOfBounds(paddr, memsz));
+            if ph.p_paddr.saturating_add(ph.p_memsz) > memory.size() as u64 {
+                return Err(errors::ElfError::SegmentOutOfBounds(ph.p_paddr, memsz));
             }
+            let paddr = ph.p_paddr as u32;
 
             let base_offset = ph.p_offset as usize;
             for i in 0..filesz {
@@ -62,8 +61,54 @@ impl ElfLoader {
             }
         }
 
+        let entry = u32::try_from(elf.header.e_entry)
+            .map_err(|_| errors::ElfError::EntryOutOfRange(elf.header.e_entry))?;
         Ok(LoadResult {
-            entry: elf.header.e_entry as u32,
+            entry,
+            is_64: elf.is_64,
         })
     }
+
+    /// Reads the `.text` section of the ELF at `path` without loading it.
+    pub fn text_section<P: AsRef<Path>>(path: P) -> Result<TextSection, errors::ElfError> {
+        let buffer = read_elf(path)?;
+        let elf = Elf::parse(&buffer)?;
+        let text = elf
+            .section_headers
+            .iter()
+            .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(".text"))
+            .ok_or(errors::ElfError::MissingSection(".text"))?;
+        let addr = u32::try_from(text.sh_addr)
+            .map_err(|_| errors::ElfError::SectionOutOfRange(text.sh_addr))?;
+        let range = text
+            .file_range()
+            .ok_or(errors::ElfError::MissingSection(".text"))?;
+        let bytes = buffer
+            .get(range)
+            .ok_or(errors::ElfError::MissingSection(".text"))?
+            .to_vec();
+        Ok(TextSection {
+            addr,
+            bytes,
+            is_64: elf.is_64,
+        })
+    }
+}
+
+/// Reads the ELF at `path`, rejecting anything but little-endian
+/// ELFCLASS32/ELFCLASS64 images.
+fn read_elf<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, errors::ElfError> {
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    use goblin::elf::header::{EI_CLASS, EI_DATA, ELFCLASS32, ELFCLASS64, ELFDATA2LSB};
+    if buffer.len() > EI_DATA {
+        let class = buffer[EI_CLASS];
+        let data = buffer[EI_DATA];
+        if !(class == ELFCLASS32 || class == ELFCLASS64) || data != ELFDATA2LSB {
+            return Err(errors::ElfError::UnsupportedFormat(class, data));
+        }
+    }
+    Ok(buffer)
 }
@@ -1,6 +1,6 @@
 use super::*;
 use crate::{compiler::errors::ElfError, memory::Memory};
-use goblin::elf::header::{ELFCLASS32, ELFDATA2LSB};
+use goblin::elf::header::{ELFCLASS32, ELFCLASS64, ELFDATA2LSB};
 use std::io::Write;
 use tempfile::NamedTempFile;
 
@@ -115,3 +115,52 @@ fn test_segment_out_of_bounds() {
         panic!("expected SegmentOutOfBounds");
     }
 }
+
+#[test]
+fn test_load_elf64() {
+    let mut elf = Vec::new();
+    let mut ehdr = [0u8; 64];
+    ehdr[0..4].copy_from_slice(&[0x7F, b'E', b'L', b'F']);
+    ehdr[4] = ELFCLASS64;
+    ehdr[5] = ELFDATA2LSB;
+    let entry = 0x40u64;
+    ehdr[24..32].copy_from_slice(&entry.to_le_bytes());
+    ehdr[32..40].copy_from_slice(&64u64.to_le_bytes());
+    ehdr[54..56].copy_from_slice(&56u16.to_le_bytes());
+    ehdr[56..58].copy_from_slice(&1u16.to_le_bytes());
+    elf.extend_from_slice(&ehdr);
+    let mut phdr = [0u8; 56];
+    phdr[0..4].copy_from_slice(&1u32.to_le_bytes());
+    phdr[8..16].copy_from_slice(&120u64.to_le_bytes());
+    phdr[24..32].copy_from_slice(&0x40u64.to_le_bytes());
+    phdr[32..40].copy_from_slice(&4u64.to_le_bytes());
+    phdr[40..48].copy_from_slice(&4u64.to_le_bytes());
+    elf.extend_from_slice(&phdr);
+    elf.extend_from_slice(&0x0000_0073u32.to_le_bytes());
+
+    let mut tmp = NamedTempFile::new().unwrap();
+    tmp.write_all(&elf).unwrap();
+
+    let mut mem = Memory::new(128).unwrap();
+    let res = ElfLoader::load_elf(tmp.path(), &mut mem).unwrap();
+    assert_eq!(res.entry, 0x40);
+    assert!(res.is_64);
+    assert_eq!(mem.load_word(0x40).unwrap(), 0x0000_0073);
+}
+
+#[test]
+fn test_elf64_entry_out_of_range() {
+    let mut ehdr = [0u8; 64];
+    ehdr[0..4].copy_from_slice(&[0x7F, b'E', b'L', b'F']);
+    ehdr[4] = ELFCLASS64;
+    ehdr[5] = ELFDATA2LSB;
+    ehdr[24..32].copy_from_slice(&(1u64 << 32).to_le_bytes());
+    ehdr[32..40].copy_from_slice(&64u64.to_le_bytes());
+
+    let mut tmp = NamedTempFile::new().unwrap();
+    tmp.write_all(&ehdr).unwrap();
+
+    let mut mem = Memory::new(128).unwrap();
+    let err = ElfLoader::load_elf(tmp.path(), &mut mem).unwrap_err();
+    assert!(matches!(err, ElfError::EntryOutOfRange(0x1_0000_0000)));
+}
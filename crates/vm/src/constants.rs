@@ -1,2 +1,36 @@
 pub const MAX_MEMORY_SIZE: usize = 64 * 1024 * 1024;
 pub const DEFAULT_MEMORY_SIZE: usize = 128;
+
+pub const REG_SP: usize = 2;
+pub const REG_T0: usize = 5;
+pub const REG_A0: usize = 10;
+pub const REG_A7: usize = 17;
+
+pub const SYS_WRITE: u32 = 64;
+pub const SYS_EXIT: u32 = 93;
+
+pub const CSR_CYCLE: u16 = 0xC00;
+pub const CSR_TIME: u16 = 0xC01;
+pub const CSR_INSTRET: u16 = 0xC02;
+pub const CSR_CYCLEH: u16 = 0xC80;
+pub const CSR_TIMEH: u16 = 0xC81;
+pub const CSR_INSTRETH: u16 = 0xC82;
+pub const CSR_MCYCLE: u16 = 0xB00;
+pub const CSR_MINSTRET: u16 = 0xB02;
+pub const CSR_MCYCLEH: u16 = 0xB80;
+pub const CSR_MINSTRETH: u16 = 0xB82;
+pub const CSR_SATP: u16 = 0x180;
+pub const CSR_MSTATUS: u16 = 0x300;
+pub const CSR_MIE: u16 = 0x304;
+pub const CSR_MTVEC: u16 = 0x305;
+pub const CSR_MSCRATCH: u16 = 0x340;
+pub const CSR_MEPC: u16 = 0x341;
+pub const CSR_MCAUSE: u16 = 0x342;
+pub const CSR_MTVAL: u16 = 0x343;
+pub const CSR_MIP: u16 = 0x344;
+pub const CSR_PMPCFG0: u16 = 0x3A0;
+pub const CSR_PMPADDR0: u16 = 0x3B0;
+
+pub const PMP_ENTRIES: usize = 16;
+pub const CSR_PMPCFG_LAST: u16 = CSR_PMPCFG0 + PMP_ENTRIES as u16 / 4 - 1;
+pub const CSR_PMPADDR_LAST: u16 = CSR_PMPADDR0 + PMP_ENTRIES as u16 - 1;
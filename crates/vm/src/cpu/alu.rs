@@ -0,0 +1,170 @@
+use std::ops::{BitAnd, BitOr, BitXor};
+
+use crate::itxs::Instruction;
+
+/// A register width the integer semantics are generic over: `u32` for the
+/// RV32 [`CPU`](super::CPU), `u64` for [`CPU64`](crate::cpu64::CPU64).
+pub(crate) trait Xlen:
+    Copy + Ord + From<bool> + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self>
+{
+    /// Sign-extends an instruction immediate to the register width.
+    fn from_imm(imm: i32) -> Self;
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    fn wrapping_mul(self, rhs: Self) -> Self;
+    fn lt_signed(self, rhs: Self) -> bool;
+    /// Shifts use the low log2(XLEN) bits of `amt`, like SLL/SRL/SRA.
+    fn shl(self, amt: Self) -> Self;
+    fn shr(self, amt: Self) -> Self;
+    fn sra(self, amt: Self) -> Self;
+    /// Upper half of the signed x signed, signed x unsigned and unsigned x
+    /// unsigned products.
+    fn mulh(self, rhs: Self) -> Self;
+    fn mulhsu(self, rhs: Self) -> Self;
+    fn mulhu(self, rhs: Self) -> Self;
+    /// Division never traps: dividing by zero gives all ones for the
+    /// quotient and the dividend for the remainder, and signed overflow
+    /// wraps.
+    fn div(self, rhs: Self) -> Self;
+    fn divu(self, rhs: Self) -> Self;
+    fn rem(self, rhs: Self) -> Self;
+    fn remu(self, rhs: Self) -> Self;
+}
+
+macro_rules! impl_xlen {
+    ($u:ty, $i:ty, $wide_u:ty, $wide_i:ty) => {
+        impl Xlen for $u {
+            fn from_imm(imm: i32) -> Self {
+                imm as $i as $u
+            }
+
+            fn wrapping_add(self, rhs: Self) -> Self {
+                <$u>::wrapping_add(self, rhs)
+            }
+
+            fn wrapping_sub(self, rhs: Self) -> Self {
+                <$u>::wrapping_sub(self, rhs)
+            }
+
+            fn wrapping_mul(self, rhs: Self) -> Self {
+                <$u>::wrapping_mul(self, rhs)
+            }
+
+            fn lt_signed(self, rhs: Self) -> bool {
+                (self as $i) < (rhs as $i)
+            }
+
+            fn shl(self, amt: Self) -> Self {
+                self.wrapping_shl(amt as u32)
+            }
+
+            fn shr(self, amt: Self) -> Self {
+                self.wrapping_shr(amt as u32)
+            }
+
+            fn sra(self, amt: Self) -> Self {
+                (self as $i).wrapping_shr(amt as u32) as $u
+            }
+
+            fn mulh(self, rhs: Self) -> Self {
+                let product = self as $i as $wide_i * rhs as $i as $wide_i;
+                (product >> <$u>::BITS) as $u
+            }
+
+            fn mulhsu(self, rhs: Self) -> Self {
+                let product = self as $i as $wide_i * rhs as $wide_u as $wide_i;
+                (product >> <$u>::BITS) as $u
+            }
+
+            fn mulhu(self, rhs: Self) -> Self {
+                let product = self as $wide_u * rhs as $wide_u;
+                (product >> <$u>::BITS) as $u
+            }
+
+            fn div(self, rhs: Self) -> Self {
+                if rhs == 0 {
+                    <$u>::MAX
+                } else {
+                    (self as $i).wrapping_div(rhs as $i) as $u
+                }
+            }
+
+            fn divu(self, rhs: Self) -> Self {
+                if rhs == 0 { <$u>::MAX } else { self / rhs }
+            }
+
+            fn rem(self, rhs: Self) -> Self {
+                if rhs == 0 {
+                    self
+                } else {
+                    (self as $i).wrapping_rem(rhs as $i) as $u
+                }
+            }
+
+            fn remu(self, rhs: Self) -> Self {
+                if rhs == 0 { self } else { self % rhs }
+            }
+        }
+    };
+}
+
+impl_xlen!(u32, i32, u64, i64);
+impl_xlen!(u64, i64, u128, i128);
+
+/// Evaluates the register-immediate and register-register ops RV32 and RV64
+/// share (the I base plus M), reading sources through `reg`. Returns the
+/// destination register and its new value, or `None` for any other
+/// instruction.
+pub(crate) fn alu<X: Xlen>(instr: &Instruction, reg: impl Fn(u8) -> X) -> Option<(u8, X)> {
+    use Instruction::*;
+    let imm = X::from_imm;
+    let res = match *instr {
+        ADDI { rd, rs1, imm: i } => (rd, reg(rs1).wrapping_add(imm(i))),
+        SLTI { rd, rs1, imm: i } => (rd, reg(rs1).lt_signed(imm(i)).into()),
+        SLTIU { rd, rs1, imm: i } => (rd, (reg(rs1) < imm(i)).into()),
+        XORI { rd, rs1, imm: i } => (rd, reg(rs1) ^ imm(i)),
+        ORI { rd, rs1, imm: i } => (rd, reg(rs1) | imm(i)),
+        ANDI { rd, rs1, imm: i } => (rd, reg(rs1) & imm(i)),
+        SLLI { rd, rs1, shamt } => (rd, reg(rs1).shl(imm(shamt as i32))),
+        SRLI { rd, rs1, shamt } => (rd, reg(rs1).shr(imm(shamt as i32))),
+        SRAI { rd, rs1, shamt } => (rd, reg(rs1).sra(imm(shamt as i32))),
+
+        ADD { rd, rs1, rs2 } => (rd, reg(rs1).wrapping_add(reg(rs2))),
+        SUB { rd, rs1, rs2 } => (rd, reg(rs1).wrapping_sub(reg(rs2))),
+        SLL { rd, rs1, rs2 } => (rd, reg(rs1).shl(reg(rs2))),
+        SLT { rd, rs1, rs2 } => (rd, reg(rs1).lt_signed(reg(rs2)).into()),
+        SLTU { rd, rs1, rs2 } => (rd, (reg(rs1) < reg(rs2)).into()),
+        XOR { rd, rs1, rs2 } => (rd, reg(rs1) ^ reg(rs2)),
+        SRL { rd, rs1, rs2 } => (rd, reg(rs1).shr(reg(rs2))),
+        SRA { rd, rs1, rs2 } => (rd, reg(rs1).sra(reg(rs2))),
+        OR { rd, rs1, rs2 } => (rd, reg(rs1) | reg(rs2)),
+        AND { rd, rs1, rs2 } => (rd, reg(rs1) & reg(rs2)),
+
+        MUL { rd, rs1, rs2 } => (rd, reg(rs1).wrapping_mul(reg(rs2))),
+        MULH { rd, rs1, rs2 } => (rd, reg(rs1).mulh(reg(rs2))),
+        MULHSU { rd, rs1, rs2 } => (rd, reg(rs1).mulhsu(reg(rs2))),
+        MULHU { rd, rs1, rs2 } => (rd, reg(rs1).mulhu(reg(rs2))),
+        DIV { rd, rs1, rs2 } => (rd, reg(rs1).div(reg(rs2))),
+        DIVU { rd, rs1, rs2 } => (rd, reg(rs1).divu(reg(rs2))),
+        REM { rd, rs1, rs2 } => (rd, reg(rs1).rem(reg(rs2))),
+        REMU { rd, rs1, rs2 } => (rd, reg(rs1).remu(reg(rs2))),
+        _ => return None,
+    };
+    Some(res)
+}
+
+/// Whether the conditional branch `instr` is taken, reading its operands
+/// through `reg`, or `None` if it is not a branch.
+pub(crate) fn branch_taken<X: Xlen>(instr: &Instruction, reg: impl Fn(u8) -> X) -> Option<bool> {
+    use Instruction::*;
+    let taken = match *instr {
+        BEQ { rs1, rs2, .. } => reg(rs1) == reg(rs2),
+        BNE { rs1, rs2, .. } => reg(rs1) != reg(rs2),
+        BLT { rs1, rs2, .. } => reg(rs1).lt_signed(reg(rs2)),
+        BGE { rs1, rs2, .. } => !reg(rs1).lt_signed(reg(rs2)),
+        BLTU { rs1, rs2, .. } => reg(rs1) < reg(rs2),
+        BGEU { rs1, rs2, .. } => reg(rs1) >= reg(rs2),
+        _ => return None,
+    };
+    Some(taken)
+}
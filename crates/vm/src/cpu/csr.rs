@@ -0,0 +1,280 @@
+use crate::{
+    constants::{
+        CSR_CYCLE, CSR_CYCLEH, CSR_INSTRET, CSR_INSTRETH, CSR_MCAUSE, CSR_MCYCLE, CSR_MCYCLEH,
+        CSR_MEPC, CSR_MIE, CSR_MINSTRET, CSR_MINSTRETH, CSR_MIP, CSR_MSCRATCH, CSR_MSTATUS,
+        CSR_MTVAL, CSR_MTVEC, CSR_PMPADDR_LAST, CSR_PMPADDR0, CSR_PMPCFG_LAST, CSR_PMPCFG0,
+        CSR_SATP, CSR_TIME, CSR_TIMEH,
+    },
+    cpu::{
+        errors::CPUError,
+        mmu::{SATP_MODE_SV32, SATP_PPN},
+        pmp::Pmp,
+        trap::Interrupt,
+    },
+    memory::clint::{MIP_MSIP, MIP_MTIP},
+};
+
+pub const MSTATUS_MIE: u32 = 1 << 3;
+pub const MSTATUS_MPIE: u32 = 1 << 7;
+pub const MSTATUS_MPP: u32 = 0x3 << 11;
+
+/// The privilege level a hart runs at. Only machine and user mode exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Privilege {
+    User = 0,
+    #[default]
+    Machine = 3,
+}
+
+impl Privilege {
+    /// Whether code at this level may access `csr`, whose address bits 9:8
+    /// hold the lowest privilege allowed to use it.
+    pub fn can_access(self, csr: u16) -> bool {
+        (csr >> 8) & 0x3 <= self as u16
+    }
+}
+
+/// The control and status registers of a single hart.
+///
+/// Every retired instruction costs one cycle, so `cycle` and `instret` move in
+/// lockstep unless the guest rewrites one of them through its machine-mode
+/// alias. The instruction doing such a write does not count towards the
+/// counter it wrote. `time` and the pending bits in `mip` are mirrored from
+/// the CLINT by the CPU.
+#[derive(Debug, Default)]
+pub struct CsrFile {
+    cycle: u64,
+    instret: u64,
+    cycle_written: bool,
+    instret_written: bool,
+    time: u64,
+    satp: u32,
+    mstatus: u32,
+    mie: u32,
+    mip: u32,
+    mtvec: u32,
+    mscratch: u32,
+    mepc: u32,
+    mcause: u32,
+    mtval: u32,
+    pmp: Pmp,
+}
+
+impl CsrFile {
+    pub fn new() -> Self {
+        CsrFile::default()
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    pub fn instret(&self) -> u64 {
+        self.instret
+    }
+
+    pub fn satp(&self) -> u32 {
+        self.satp
+    }
+
+    pub fn pmp(&self) -> &Pmp {
+        &self.pmp
+    }
+
+    pub fn mstatus(&self) -> u32 {
+        self.mstatus
+    }
+
+    pub fn mie(&self) -> u32 {
+        self.mie
+    }
+
+    pub fn mip(&self) -> u32 {
+        self.mip
+    }
+
+    /// Mirrors the platform's clock and interrupt lines into `time`/`mip`.
+    pub fn sync(&mut self, time: u64, pending: u32) {
+        self.time = time;
+        self.mip = pending;
+    }
+
+    /// The highest-priority interrupt that is both pending and enabled for
+    /// a hart at `privilege`. M-mode only takes interrupts with
+    /// `mstatus.MIE` set; U-mode is always interruptible.
+    pub fn pending_interrupt(&self, privilege: Privilege) -> Option<Interrupt> {
+        if privilege == Privilege::Machine && self.mstatus & MSTATUS_MIE == 0 {
+            return None;
+        }
+        let ready = self.mip & self.mie;
+        if ready & MIP_MSIP != 0 {
+            Some(Interrupt::MachineSoftware)
+        } else if ready & MIP_MTIP != 0 {
+            Some(Interrupt::MachineTimer)
+        } else {
+            None
+        }
+    }
+
+    pub fn mtvec(&self) -> u32 {
+        self.mtvec
+    }
+
+    pub fn mepc(&self) -> u32 {
+        self.mepc
+    }
+
+    pub fn mcause(&self) -> u32 {
+        self.mcause
+    }
+
+    pub fn mtval(&self) -> u32 {
+        self.mtval
+    }
+
+    /// Records a trap taken at `epc` from `from` and returns the handler
+    /// address. Exceptions always enter at the `mtvec` base, even in
+    /// vectored mode.
+    pub fn enter_trap(&mut self, epc: u32, cause: u32, tval: u32, from: Privilege) -> u32 {
+        self.push_trap(epc, cause, tval, from);
+        self.mtvec & !0x3
+    }
+
+    /// Like [`CsrFile::enter_trap`] for an interrupt, which vectored mode
+    /// sends to `base + 4 * code`.
+    pub fn enter_interrupt(&mut self, epc: u32, irq: Interrupt, from: Privilege) -> u32 {
+        self.push_trap(epc, irq.cause(), 0, from);
+        let base = self.mtvec & !0x3;
+        if self.mtvec & 0x3 == 1 {
+            base.wrapping_add(4 * irq.code())
+        } else {
+            base
+        }
+    }
+
+    fn push_trap(&mut self, epc: u32, cause: u32, tval: u32, from: Privilege) {
+        self.mepc = epc;
+        self.mcause = cause;
+        self.mtval = tval;
+        let mie = self.mstatus & MSTATUS_MIE != 0;
+        self.mstatus &= !(MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP);
+        if mie {
+            self.mstatus |= MSTATUS_MPIE;
+        }
+        self.mstatus |= (from as u32) << 11;
+    }
+
+    /// Unwinds the trap state for MRET, returning where to resume and at
+    /// which privilege level.
+    pub fn leave_trap(&mut self) -> (u32, Privilege) {
+        let privilege = if self.mstatus & MSTATUS_MPP == 0 {
+            Privilege::User
+        } else {
+            Privilege::Machine
+        };
+        let mpie = self.mstatus & MSTATUS_MPIE != 0;
+        self.mstatus &= !(MSTATUS_MIE | MSTATUS_MPP);
+        if mpie {
+            self.mstatus |= MSTATUS_MIE;
+        }
+        self.mstatus |= MSTATUS_MPIE;
+        (self.mepc, privilege)
+    }
+
+    /// Accounts for one retired instruction, unless it wrote the counter
+    /// itself.
+    pub fn retire(&mut self) {
+        if !std::mem::take(&mut self.cycle_written) {
+            self.cycle = self.cycle.wrapping_add(1);
+        }
+        if !std::mem::take(&mut self.instret_written) {
+            self.instret = self.instret.wrapping_add(1);
+        }
+    }
+
+    pub fn read(&self, csr: u16) -> Result<u32, CPUError> {
+        match csr {
+            CSR_CYCLE | CSR_MCYCLE => Ok(self.cycle as u32),
+            CSR_CYCLEH | CSR_MCYCLEH => Ok((self.cycle >> 32) as u32),
+            CSR_TIME => Ok(self.time as u32),
+            CSR_TIMEH => Ok((self.time >> 32) as u32),
+            CSR_INSTRET | CSR_MINSTRET => Ok(self.instret as u32),
+            CSR_INSTRETH | CSR_MINSTRETH => Ok((self.instret >> 32) as u32),
+            CSR_SATP => Ok(self.satp),
+            CSR_MSTATUS => Ok(self.mstatus),
+            CSR_MIE => Ok(self.mie),
+            CSR_MIP => Ok(self.mip),
+            CSR_MTVEC => Ok(self.mtvec),
+            CSR_MSCRATCH => Ok(self.mscratch),
+            CSR_MEPC => Ok(self.mepc),
+            CSR_MCAUSE => Ok(self.mcause),
+            CSR_MTVAL => Ok(self.mtval),
+            CSR_PMPCFG0..=CSR_PMPCFG_LAST => Ok(self.pmp.read_cfg((csr - CSR_PMPCFG0) as usize)),
+            CSR_PMPADDR0..=CSR_PMPADDR_LAST => {
+                Ok(self.pmp.read_addr((csr - CSR_PMPADDR0) as usize))
+            }
+            _ => Err(CPUError::InvalidCsr(csr)),
+        }
+    }
+
+    /// Writes `csr`. CSRs whose address has bits 11:10 set are read-only.
+    pub fn write(&mut self, csr: u16, value: u32) -> Result<(), CPUError> {
+        if csr >> 10 == 0b11 {
+            return Err(CPUError::InvalidCsr(csr));
+        }
+        match csr {
+            CSR_MCYCLE => {
+                set_lo(&mut self.cycle, value);
+                self.cycle_written = true;
+            }
+            CSR_MCYCLEH => {
+                set_hi(&mut self.cycle, value);
+                self.cycle_written = true;
+            }
+            CSR_MINSTRET => {
+                set_lo(&mut self.instret, value);
+                self.instret_written = true;
+            }
+            CSR_MINSTRETH => {
+                set_hi(&mut self.instret, value);
+                self.instret_written = true;
+            }
+            // no ASID bits are implemented
+            CSR_SATP => self.satp = value & (SATP_MODE_SV32 | SATP_PPN),
+            CSR_MSTATUS => {
+                let mut v = value & (MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP);
+                // MPP can only name a mode that exists
+                if v & MSTATUS_MPP != MSTATUS_MPP {
+                    v &= !MSTATUS_MPP;
+                }
+                self.mstatus = v;
+            }
+            CSR_MIE => self.mie = value & (MIP_MSIP | MIP_MTIP),
+            // the pending bits are driven by the CLINT, not software
+            CSR_MIP => {}
+            // only direct (0) and vectored (1) modes exist
+            CSR_MTVEC if value & 0x3 >= 2 => self.mtvec = value & !0x3,
+            CSR_MTVEC => self.mtvec = value,
+            CSR_MSCRATCH => self.mscratch = value,
+            CSR_MEPC => self.mepc = value & !0x1,
+            CSR_MCAUSE => self.mcause = value,
+            CSR_MTVAL => self.mtval = value,
+            CSR_PMPCFG0..=CSR_PMPCFG_LAST => {
+                self.pmp.write_cfg((csr - CSR_PMPCFG0) as usize, value)
+            }
+            CSR_PMPADDR0..=CSR_PMPADDR_LAST => {
+                self.pmp.write_addr((csr - CSR_PMPADDR0) as usize, value)
+            }
+            _ => return Err(CPUError::InvalidCsr(csr)),
+        }
+        Ok(())
+    }
+}
+
+fn set_lo(counter: &mut u64, value: u32) {
+    *counter = (*counter & !0xFFFF_FFFF) | value as u64;
+}
+
+fn set_hi(counter: &mut u64, value: u32) {
+    *counter = (*counter & 0xFFFF_FFFF) | ((value as u64) << 32);
+}
@@ -1,4 +1,5 @@
 use crate::decoder::errors::DecodeError;
+use crate::itxs::Instruction;
 use crate::memory::errors::MemoryError;
 
 #[derive(Debug, PartialEq)]
@@ -6,6 +7,26 @@ pub enum CPUError {
     Memory(MemoryError),
     Decode(DecodeError),
     InvalidRegister(usize),
+    UnknownSyscall(u32),
+    /// A U-mode `ecall` in [`TrapMode::Host`](super::trap::TrapMode::Host).
+    /// Host syscalls see physical memory, so only M-mode may reach them.
+    UserEcall,
+    InvalidCsr(u16),
+    /// A decoded instruction this core has no implementation for, such as
+    /// an RV64-only op handed to the 32-bit [`CPU`](super::CPU).
+    UnsupportedInstruction(Instruction),
+    /// A privileged instruction executed below the level it requires.
+    PrivilegedInstruction(Instruction),
+    /// A U-mode access to a virtual address with no valid mapping that
+    /// permits it.
+    PageFault(u32),
+    /// An access to an address that physical memory protection forbids
+    /// at the current privilege level.
+    PmpViolation(u32),
+    /// A jump or taken branch to a target that is not instruction-aligned.
+    MisalignedTarget(u32),
+    /// An RV64 effective address beyond the 32-bit guest address space.
+    AddressOutOfRange(u64),
 }
 
 impl From<MemoryError> for CPUError {
@@ -1,127 +1,468 @@
 use crate::{
-    cpu::{CPU, errors::CPUError},
+    constants::{CSR_SATP, REG_A7, REG_T0},
+    cpu::{
+        CPU, StopReason,
+        alu::{alu, branch_taken},
+        csr::Privilege,
+        errors::CPUError,
+    },
     itxs::Instruction,
+    memory::clint::MIP_MTIP,
+    syscall::SyscallContext,
 };
 
 impl CPU {
-    pub fn execute(&mut self, instr: Instruction) -> Result<(), CPUError> {
+    /// Executes `instr` as if it were fetched from the current `pc`, leaving
+    /// `pc` pointing at the next instruction to run.
+    pub fn execute(&mut self, instr: Instruction) -> Result<Option<StopReason>, CPUError> {
+        self.execute_sized(instr, 4)
+    }
+
+    /// Like [`CPU::execute`], for an instruction whose encoding is `len` bytes
+    /// long (2 for an expanded compressed instruction).
+    pub fn execute_sized(
+        &mut self,
+        instr: Instruction,
+        len: u32,
+    ) -> Result<Option<StopReason>, CPUError> {
         use Instruction::*;
+        if let Some(&reg) = instr
+            .registers()
+            .iter()
+            .find(|&&r| r as usize >= self.isa.num_regs())
+        {
+            return Err(CPUError::InvalidRegister(reg as usize));
+        }
+        let pc = self.pc;
+        let mut next_pc = pc.wrapping_add(len);
+        let mut stop = None;
         match instr {
             LUI { rd, imm } => {
                 self.write_reg(rd as usize, imm);
-                Ok(())
             }
 
             AUIPC { rd, imm } => {
-                let val = self.pc.wrapping_add(imm);
+                let val = pc.wrapping_add(imm);
                 self.write_reg(rd as usize, val);
-                Ok(())
             }
 
             JAL { rd, imm } => {
-                let ret = self.pc.wrapping_add(4);
-                self.write_reg(rd as usize, ret);
-
-                self.pc = self.pc.wrapping_add(imm as u32);
-                Ok(())
+                let target = self.jump_target(pc.wrapping_add(imm as u32))?;
+                self.write_reg(rd as usize, next_pc);
+                next_pc = target;
             }
 
             JALR { rd, rs1, imm } => {
-                let ret = self.pc.wrapping_add(4);
-                self.write_reg(rd as usize, ret);
                 let base = self.read_reg(rs1 as usize);
-                let target = base.wrapping_add(imm as u32) & !1;
-                self.pc = target;
-                Ok(())
+                let target = self.jump_target(base.wrapping_add(imm as u32) & !1)?;
+                self.write_reg(rd as usize, next_pc);
+                next_pc = target;
             }
-            OP_IMM { rd, rs1, imm } => {
-                let res = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
+            // the I and M ops RV64 shares, see `cpu::alu`
+            ADDI { .. }
+            | SLTI { .. }
+            | SLTIU { .. }
+            | XORI { .. }
+            | ORI { .. }
+            | ANDI { .. }
+            | SLLI { .. }
+            | SRLI { .. }
+            | SRAI { .. }
+            | ADD { .. }
+            | SUB { .. }
+            | SLL { .. }
+            | SLT { .. }
+            | SLTU { .. }
+            | XOR { .. }
+            | SRL { .. }
+            | SRA { .. }
+            | OR { .. }
+            | AND { .. }
+            | MUL { .. }
+            | MULH { .. }
+            | MULHSU { .. }
+            | MULHU { .. }
+            | DIV { .. }
+            | DIVU { .. }
+            | REM { .. }
+            | REMU { .. } => {
+                let (rd, res) = alu(&instr, |r| self.read_reg(r as usize)).expect("an integer op");
                 self.write_reg(rd as usize, res);
-                Ok(())
             }
-            SHIFT_IMM {
-                rd,
-                rs1,
-                shamt,
-                is_srai,
-            } => {
-                let v = self.read_reg(rs1 as usize);
-                let res = if is_srai { v >> shamt } else { v << shamt };
-                self.write_reg(rd as usize, res);
-                Ok(())
+            LB { rd, rs1, imm } => {
+                let addr = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
+                let val = self.load_byte(addr)? as i8 as u32;
+                self.write_reg(rd as usize, val);
+            }
+            LH { rd, rs1, imm } => {
+                let addr = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
+                let val = self.load_half(addr)? as i16 as u32;
+                self.write_reg(rd as usize, val);
+            }
+            LW { rd, rs1, imm } => {
+                let addr = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
+                let val = self.load_word(addr)?;
+                self.write_reg(rd as usize, val);
+            }
+            LBU { rd, rs1, imm } => {
+                let addr = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
+                let val = self.load_byte(addr)? as u32;
+                self.write_reg(rd as usize, val);
             }
-            LOAD { rd, rs1, imm } => {
+            LHU { rd, rs1, imm } => {
                 let addr = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
-                let word = crate::memory::Memory::load_word(&mut self.memory, addr)?;
-                self.write_reg(rd as usize, word);
-                Ok(())
+                let val = self.load_half(addr)? as u32;
+                self.write_reg(rd as usize, val);
             }
-            STORE { rs1, rs2, imm } => {
+            SB { rs1, rs2, imm } => {
                 let addr = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
                 let val = self.read_reg(rs2 as usize);
-                crate::memory::Memory::store_word(&mut self.memory, addr, val)?;
-                Ok(())
-            }
-            BRANCH { rs1, rs2, imm } => {
-                let v1 = self.read_reg(rs1 as usize);
-                let v2 = self.read_reg(rs2 as usize);
-                if v1 == v2 {
-                    self.pc = self.pc.wrapping_add(imm as u32);
+                self.store_byte(addr, val as u8)?;
+            }
+            SH { rs1, rs2, imm } => {
+                let addr = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
+                let val = self.read_reg(rs2 as usize);
+                self.store_half(addr, val as u16)?;
+            }
+            SW { rs1, rs2, imm } => {
+                let addr = self.read_reg(rs1 as usize).wrapping_add(imm as u32);
+                let val = self.read_reg(rs2 as usize);
+                self.store_word(addr, val)?;
+            }
+            BEQ { imm, .. }
+            | BNE { imm, .. }
+            | BLT { imm, .. }
+            | BGE { imm, .. }
+            | BLTU { imm, .. }
+            | BGEU { imm, .. } => {
+                if branch_taken(&instr, |r| self.read_reg(r as usize)) == Some(true) {
+                    next_pc = self.jump_target(pc.wrapping_add(imm as u32))?;
                 }
-                Ok(())
             }
-            OP {
-                rd,
-                rs1,
-                rs2,
-                funct7,
-            } => {
+            SH1ADD { rd, rs1, rs2 } => {
                 let a = self.read_reg(rs1 as usize);
                 let b = self.read_reg(rs2 as usize);
-                let res = match funct7 {
-                    0x00 => a.wrapping_add(b),
-                    0x20 => a.wrapping_sub(b),
-                    _ => return Err(CPUError::InvalidRegister(funct7 as usize)),
-                };
-                self.write_reg(rd as usize, res);
-                Ok(())
+                self.write_reg(rd as usize, (a << 1).wrapping_add(b));
             }
-            ECALL | EBREAK => Ok(()),
-            MUL { rd, rs1, rs2 } => {
-                let res = self
-                    .read_reg(rs1 as usize)
-                    .wrapping_mul(self.read_reg(rs2 as usize));
-                self.write_reg(rd as usize, res);
-                Ok(())
+            SH2ADD { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, (a << 2).wrapping_add(b));
             }
-            DIV { rd, rs1, rs2 } => {
-                let a = self.read_reg(rs1 as usize) as i32;
-                let b = self.read_reg(rs2 as usize) as i32;
-                let res = if b == 0 { -1 } else { a.wrapping_div(b) };
-                self.write_reg(rd as usize, res as u32);
-                Ok(())
+            SH3ADD { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, (a << 3).wrapping_add(b));
             }
-            DIVU { rd, rs1, rs2 } => {
+            ANDN { rd, rs1, rs2 } => {
                 let a = self.read_reg(rs1 as usize);
                 let b = self.read_reg(rs2 as usize);
-                let res = if b == 0 { u32::MAX } else { a.wrapping_div(b) };
-                self.write_reg(rd as usize, res);
-                Ok(())
+                self.write_reg(rd as usize, a & !b);
+            }
+            ORN { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, a | !b);
             }
-            REM { rd, rs1, rs2 } => {
-                let a = self.read_reg(rs1 as usize) as i32;
-                let b = self.read_reg(rs2 as usize) as i32;
-                let res = if b == 0 { a } else { a.wrapping_rem(b) };
-                self.write_reg(rd as usize, res as u32);
-                Ok(())
+            XNOR { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, !(a ^ b));
             }
-            REMU { rd, rs1, rs2 } => {
+            MIN { rd, rs1, rs2 } => {
                 let a = self.read_reg(rs1 as usize);
                 let b = self.read_reg(rs2 as usize);
-                let res = if b == 0 { a } else { a.wrapping_rem(b) };
-                self.write_reg(rd as usize, res);
-                Ok(())
+                self.write_reg(rd as usize, (a as i32).min(b as i32) as u32);
+            }
+            MINU { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, a.min(b));
+            }
+            MAX { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, (a as i32).max(b as i32) as u32);
+            }
+            MAXU { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, a.max(b));
+            }
+            ROL { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, a.rotate_left(b & 0x1F));
+            }
+            ROR { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, a.rotate_right(b & 0x1F));
+            }
+            BCLR { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, a & !(1 << (b & 0x1F)));
             }
+            BEXT { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, (a >> (b & 0x1F)) & 1);
+            }
+            BINV { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, a ^ (1 << (b & 0x1F)));
+            }
+            BSET { rd, rs1, rs2 } => {
+                let a = self.read_reg(rs1 as usize);
+                let b = self.read_reg(rs2 as usize);
+                self.write_reg(rd as usize, a | (1 << (b & 0x1F)));
+            }
+            CLZ { rd, rs1 } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v.leading_zeros());
+            }
+            CTZ { rd, rs1 } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v.trailing_zeros());
+            }
+            CPOP { rd, rs1 } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v.count_ones());
+            }
+            SEXT_B { rd, rs1 } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v as i8 as u32);
+            }
+            SEXT_H { rd, rs1 } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v as i16 as u32);
+            }
+            ZEXT_H { rd, rs1 } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v & 0xFFFF);
+            }
+            ORC_B { rd, rs1 } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, orc_b(v));
+            }
+            REV8 { rd, rs1 } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v.swap_bytes());
+            }
+            RORI { rd, rs1, shamt } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v.rotate_right(shamt as u32));
+            }
+            BCLRI { rd, rs1, shamt } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v & !(1 << shamt));
+            }
+            BEXTI { rd, rs1, shamt } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, (v >> shamt) & 1);
+            }
+            BINVI { rd, rs1, shamt } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v ^ (1 << shamt));
+            }
+            BSETI { rd, rs1, shamt } => {
+                let v = self.read_reg(rs1 as usize);
+                self.write_reg(rd as usize, v | (1 << shamt));
+            }
+            // A single hart with no caches observes its own memory accesses in
+            // program order, so fences have nothing to order. The VM does not
+            // cache decoded instructions either, so FENCE.I has nothing to
+            // invalidate: every step fetches from memory.
+            FENCE { .. } | FENCE_I => {}
+            ECALL => stop = self.ecall()?,
+            EBREAK => stop = Some(StopReason::Breakpoint),
+            WFI => {
+                if self.privilege != Privilege::Machine {
+                    return Err(CPUError::PrivilegedInstruction(instr));
+                }
+                stop = self.wait_for_interrupt();
+            }
+            SFENCE_VMA { rs1, .. } => {
+                if self.privilege != Privilege::Machine {
+                    return Err(CPUError::PrivilegedInstruction(instr));
+                }
+                let vaddr = (rs1 != 0).then(|| self.read_reg(rs1 as usize));
+                self.mmu.flush(vaddr);
+            }
+            MRET => {
+                if self.privilege != Privilege::Machine {
+                    return Err(CPUError::PrivilegedInstruction(instr));
+                }
+                let (target, privilege) = self.csrs.leave_trap();
+                self.privilege = privilege;
+                next_pc = target;
+            }
+            LR_W { rd, rs1, .. } => {
+                let addr = self.read_reg(rs1 as usize);
+                let val = self.load_word(addr)?;
+                self.reservation = Some(addr);
+                self.write_reg(rd as usize, val);
+            }
+            SC_W { rd, rs1, rs2, .. } => {
+                let addr = self.read_reg(rs1 as usize);
+                let val = self.read_reg(rs2 as usize);
+                let reserved = self.reservation.take() == Some(addr);
+                if reserved {
+                    self.store_word(addr, val)?;
+                }
+                self.write_reg(rd as usize, !reserved as u32);
+            }
+            AMOSWAP_W { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, |_, b| b)?,
+            AMOADD_W { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, |a, b| a.wrapping_add(b))?,
+            AMOXOR_W { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, |a, b| a ^ b)?,
+            AMOAND_W { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, |a, b| a & b)?,
+            AMOOR_W { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, |a, b| a | b)?,
+            AMOMIN_W { rd, rs1, rs2, .. } => {
+                self.amo(rd, rs1, rs2, |a, b| (a as i32).min(b as i32) as u32)?
+            }
+            AMOMAX_W { rd, rs1, rs2, .. } => {
+                self.amo(rd, rs1, rs2, |a, b| (a as i32).max(b as i32) as u32)?
+            }
+            AMOMINU_W { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, |a, b| a.min(b))?,
+            AMOMAXU_W { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, |a, b| a.max(b))?,
+            CSRRW { rd, rs1, csr } => {
+                let v = self.read_reg(rs1 as usize);
+                self.csr_update(rd, csr, true, |_| v)?;
+            }
+            CSRRS { rd, rs1, csr } => {
+                let v = self.read_reg(rs1 as usize);
+                self.csr_update(rd, csr, rs1 != 0, |old| old | v)?;
+            }
+            CSRRC { rd, rs1, csr } => {
+                let v = self.read_reg(rs1 as usize);
+                self.csr_update(rd, csr, rs1 != 0, |old| old & !v)?;
+            }
+            CSRRWI { rd, uimm, csr } => {
+                self.csr_update(rd, csr, true, |_| uimm as u32)?;
+            }
+            CSRRSI { rd, uimm, csr } => {
+                self.csr_update(rd, csr, uimm != 0, |old| old | uimm as u32)?;
+            }
+            CSRRCI { rd, uimm, csr } => {
+                self.csr_update(rd, csr, uimm != 0, |old| old & !(uimm as u32))?;
+            }
+            LWU { .. }
+            | LD { .. }
+            | SD { .. }
+            | ADDIW { .. }
+            | SLLIW { .. }
+            | SRLIW { .. }
+            | SRAIW { .. }
+            | ADDW { .. }
+            | SUBW { .. }
+            | SLLW { .. }
+            | SRLW { .. }
+            | SRAW { .. }
+            | MULW { .. }
+            | DIVW { .. }
+            | DIVUW { .. }
+            | REMW { .. }
+            | REMUW { .. } => return Err(CPUError::UnsupportedInstruction(instr)),
+        }
+        self.pc = next_pc;
+        self.csrs.retire();
+        Ok(stop)
+    }
+
+    /// Idles until an interrupt enabled in `mie` is pending, whether or not
+    /// `mstatus.MIE` lets it trap. Time only moves when the hart does, so a
+    /// timer that is armed is fast-forwarded to its deadline; with nothing
+    /// that could ever fire, control goes back to the host.
+    fn wait_for_interrupt(&mut self) -> Option<StopReason> {
+        let clint = &mut self.memory.clint;
+        let mie = self.csrs.mie();
+        if clint.pending() & mie != 0 {
+            return None;
+        }
+        if mie & MIP_MTIP != 0 && clint.mtimecmp() != u64::MAX {
+            clint.set_mtime(clint.mtimecmp());
+            return None;
+        }
+        Some(StopReason::WaitForInterrupt)
+    }
+
+    /// Checks that a jump or taken branch lands on an instruction boundary:
+    /// two bytes with the C extension, four without.
+    fn jump_target(&self, target: u32) -> Result<u32, CPUError> {
+        let align = if self.isa.c { 2 } else { 4 };
+        if !target.is_multiple_of(align) {
+            return Err(CPUError::MisalignedTarget(target));
+        }
+        Ok(target)
+    }
+
+    /// Atomically replaces the word at `rs1` with `op(old, rs2)` and returns
+    /// the old value in `rd`. With a single hart there is nothing to order
+    /// against, so the aq/rl bits need no extra handling.
+    fn amo<F>(&mut self, rd: u8, rs1: u8, rs2: u8, op: F) -> Result<(), CPUError>
+    where
+        F: FnOnce(u32, u32) -> u32,
+    {
+        let addr = self.read_reg(rs1 as usize);
+        let src = self.read_reg(rs2 as usize);
+        let old = self.load_word(addr)?;
+        self.store_word(addr, op(old, src))?;
+        self.write_reg(rd as usize, old);
+        Ok(())
+    }
+
+    /// Reads `csr` into `rd` and, when `write` is set, replaces it with
+    /// `update(old)`. CSRRS/CSRRC with x0 (or a zero immediate) don't write, so
+    /// they can read read-only CSRs.
+    fn csr_update<F>(&mut self, rd: u8, csr: u16, write: bool, update: F) -> Result<(), CPUError>
+    where
+        F: FnOnce(u32) -> u32,
+    {
+        if !self.privilege.can_access(csr) {
+            return Err(CPUError::InvalidCsr(csr));
+        }
+        let old = self.csrs.read(csr)?;
+        if write {
+            self.csrs.write(csr, update(old))?;
+            // a new root table invalidates every cached translation
+            if csr == CSR_SATP {
+                self.mmu.flush(None);
+            }
+        }
+        self.write_reg(rd as usize, old);
+        Ok(())
+    }
+
+    /// Dispatches an environment call to the handler registered for the
+    /// syscall number in a7. Arguments are passed in a0..a6.
+    ///
+    /// Handlers get untranslated memory, so U-mode callers are refused
+    /// rather than let past their page tables and PMP.
+    fn ecall(&mut self) -> Result<Option<StopReason>, CPUError> {
+        if self.privilege == Privilege::User {
+            return Err(CPUError::UserEcall);
+        }
+        // RV32E has no a7, so its ABI passes the syscall number in t0.
+        let num = self.read_reg(if self.isa.e { REG_T0 } else { REG_A7 });
+        let num_regs = self.isa.num_regs();
+        let mut ctx = SyscallContext::new(&mut self.regs[..num_regs], &mut self.memory);
+        let stop = self.syscalls.dispatch(num, &mut ctx)?;
+        if let Some(StopReason::Halt(code)) = stop {
+            self.exit_code = Some(code);
+        }
+        Ok(stop)
+    }
+}
+
+/// Sets every byte of `v` that has any bit set to 0xFF.
+fn orc_b(v: u32) -> u32 {
+    let mut bytes = v.to_le_bytes();
+    for b in bytes.iter_mut() {
+        if *b != 0 {
+            *b = 0xFF;
         }
     }
+    u32::from_le_bytes(bytes)
 }
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use crate::{
+    cpu::{CPU, csr::Privilege, errors::CPUError},
+    memory::{Memory, errors::MemoryError},
+};
+
+pub const SATP_MODE_SV32: u32 = 1 << 31;
+pub const SATP_PPN: u32 = 0x003F_FFFF;
+
+pub const PTE_V: u32 = 1 << 0;
+pub const PTE_R: u32 = 1 << 1;
+pub const PTE_W: u32 = 1 << 2;
+pub const PTE_X: u32 = 1 << 3;
+pub const PTE_U: u32 = 1 << 4;
+pub const PTE_A: u32 = 1 << 6;
+pub const PTE_D: u32 = 1 << 7;
+
+const PAGE_SHIFT: u32 = 12;
+/// Translations are dropped wholesale once the TLB holds this many pages.
+const TLB_CAPACITY: usize = 256;
+
+/// What a virtual address is being translated for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Fetch,
+    Load,
+    Store,
+}
+
+/// A cached leaf translation for one 4 KiB page. Superpages are cached
+/// page by page.
+#[derive(Debug, Clone, Copy)]
+struct TlbEntry {
+    ppn: u32,
+    flags: u32,
+    /// Whether the leaf PTE was a megapage, shared by all 1024 pages with
+    /// the same VPN[1].
+    megapage: bool,
+}
+
+/// An Sv32 page-table walker with a TLB in front of it.
+///
+/// Accessed and dirty bits are never set by the walker: a page whose PTE
+/// lacks A, or D for a store, faults so that the guest can update it.
+#[derive(Debug, Default)]
+pub struct Mmu {
+    tlb: HashMap<u32, TlbEntry>,
+}
+
+impl Mmu {
+    /// Translates `vaddr` under the root table named by `satp`.
+    pub fn translate(
+        &mut self,
+        memory: &Memory,
+        satp: u32,
+        vaddr: u32,
+        access: Access,
+    ) -> Result<u32, CPUError> {
+        let vpn = vaddr >> PAGE_SHIFT;
+        let entry = match self.tlb.get(&vpn) {
+            Some(entry) => *entry,
+            None => {
+                let entry = walk(memory, satp, vaddr)?;
+                if self.tlb.len() >= TLB_CAPACITY {
+                    self.tlb.clear();
+                }
+                self.tlb.insert(vpn, entry);
+                entry
+            }
+        };
+        check_permissions(entry.flags, vaddr, access)?;
+        Ok((entry.ppn << PAGE_SHIFT) | (vaddr & 0xFFF))
+    }
+
+    /// SFENCE.VMA: forgets the translation for `vaddr`, including every
+    /// page cached from a megapage covering it, or every translation when
+    /// `vaddr` is `None`.
+    pub fn flush(&mut self, vaddr: Option<u32>) {
+        match vaddr {
+            Some(vaddr) => {
+                let vpn = vaddr >> PAGE_SHIFT;
+                self.tlb.retain(|&page, entry| {
+                    page != vpn && !(entry.megapage && page >> 10 == vpn >> 10)
+                });
+            }
+            None => self.tlb.clear(),
+        }
+    }
+}
+
+/// Walks the two-level Sv32 table for `vaddr`. Only user pages can be
+/// reached, since M-mode accesses are never translated.
+fn walk(memory: &Memory, satp: u32, vaddr: u32) -> Result<TlbEntry, CPUError> {
+    let vpn = [(vaddr >> 12) & 0x3FF, vaddr >> 22];
+    let mut table = ((satp & SATP_PPN) as u64) << PAGE_SHIFT;
+    for level in (0..2).rev() {
+        let pte_addr = table + vpn[level] as u64 * 4;
+        let pte = physical(memory, pte_addr, vaddr)
+            .and_then(|addr| memory.load_word(addr).map_err(|e| at(e, vaddr)))?;
+        if pte & PTE_V == 0 || (pte & PTE_R == 0 && pte & PTE_W != 0) {
+            return Err(CPUError::PageFault(vaddr));
+        }
+        let ppn = (pte >> 10) as u64;
+        if pte & (PTE_R | PTE_X) == 0 {
+            table = ppn << PAGE_SHIFT;
+            continue;
+        }
+        if pte & PTE_U == 0 {
+            return Err(CPUError::PageFault(vaddr));
+        }
+        let ppn = if level == 1 {
+            // a megapage must be aligned to 4 MiB
+            if ppn & 0x3FF != 0 {
+                return Err(CPUError::PageFault(vaddr));
+            }
+            ppn | vpn[0] as u64
+        } else {
+            ppn
+        };
+        let base = physical(memory, ppn << PAGE_SHIFT, vaddr)?;
+        return Ok(TlbEntry {
+            ppn: base >> PAGE_SHIFT,
+            flags: pte & 0xFF,
+            megapage: level == 1,
+        });
+    }
+    Err(CPUError::PageFault(vaddr))
+}
+
+fn check_permissions(flags: u32, vaddr: u32, access: Access) -> Result<(), CPUError> {
+    let needed = match access {
+        Access::Fetch => PTE_X | PTE_A,
+        Access::Load => PTE_R | PTE_A,
+        Access::Store => PTE_W | PTE_A | PTE_D,
+    };
+    if flags & needed != needed {
+        return Err(CPUError::PageFault(vaddr));
+    }
+    Ok(())
+}
+
+/// Sv32 can name 34-bit physical addresses; anything past 4 GiB is an
+/// access fault on the original virtual address.
+fn physical(memory: &Memory, paddr: u64, vaddr: u32) -> Result<u32, CPUError> {
+    u32::try_from(paddr).map_err(|_| {
+        CPUError::Memory(MemoryError::OutOfBounds {
+            addr: vaddr,
+            size: memory.size(),
+        })
+    })
+}
+
+/// Reports a memory error against the virtual address the guest used.
+fn at(err: MemoryError, vaddr: u32) -> CPUError {
+    let err = match err {
+        MemoryError::OutOfBounds { size, .. } => MemoryError::OutOfBounds { addr: vaddr, size },
+        MemoryError::UnalignedAccess { align, .. } => {
+            MemoryError::UnalignedAccess { addr: vaddr, align }
+        }
+    };
+    CPUError::Memory(err)
+}
+
+/// Memory accessors for the executing hart. U-mode accesses go through the
+/// MMU once `satp` selects Sv32; M-mode always sees physical memory. The
+/// resulting physical access is then checked against PMP. Page-table walks
+/// are not.
+impl CPU {
+    /// Translates the `len`-byte access at `vaddr` to a physical address.
+    pub(crate) fn translate(
+        &mut self,
+        vaddr: u32,
+        len: u32,
+        access: Access,
+    ) -> Result<u32, CPUError> {
+        let satp = self.csrs.satp();
+        let paddr = if self.privilege == Privilege::Machine || satp & SATP_MODE_SV32 == 0 {
+            vaddr
+        } else {
+            self.mmu.translate(&self.memory, satp, vaddr, access)?
+        };
+        if !self.csrs.pmp().allows(paddr, len, access, self.privilege) {
+            return Err(CPUError::PmpViolation(vaddr));
+        }
+        Ok(paddr)
+    }
+
+    /// Fetches the instruction parcel at `pc`; the halves of a 32-bit
+    /// instruction are translated separately, as they may straddle pages.
+    pub(crate) fn fetch(&mut self, pc: u32) -> Result<u32, CPUError> {
+        let paddr = self.translate(pc, 2, Access::Fetch)?;
+        let lo = self.memory.load_half(paddr).map_err(|e| at(e, pc))? as u32;
+        if lo & 0x3 != 0x3 {
+            return Ok(lo);
+        }
+        let next = pc.wrapping_add(2);
+        let paddr = self.translate(next, 2, Access::Fetch)?;
+        let hi = self.memory.load_half(paddr).map_err(|e| at(e, next))? as u32;
+        Ok(lo | (hi << 16))
+    }
+
+    pub(crate) fn load_byte(&mut self, addr: u32) -> Result<u8, CPUError> {
+        let paddr = self.translate(addr, 1, Access::Load)?;
+        self.memory.load_byte(paddr).map_err(|e| at(e, addr))
+    }
+
+    pub(crate) fn load_half(&mut self, addr: u32) -> Result<u16, CPUError> {
+        let paddr = self.translate(addr, 2, Access::Load)?;
+        self.memory.load_half(paddr).map_err(|e| at(e, addr))
+    }
+
+    pub(crate) fn load_word(&mut self, addr: u32) -> Result<u32, CPUError> {
+        let paddr = self.translate(addr, 4, Access::Load)?;
+        self.memory.load_word(paddr).map_err(|e| at(e, addr))
+    }
+
+    pub(crate) fn store_byte(&mut self, addr: u32, value: u8) -> Result<(), CPUError> {
+        let paddr = self.translate(addr, 1, Access::Store)?;
+        self.memory
+            .store_byte(paddr, value)
+            .map_err(|e| at(e, addr))
+    }
+
+    pub(crate) fn store_half(&mut self, addr: u32, value: u16) -> Result<(), CPUError> {
+        let paddr = self.translate(addr, 2, Access::Store)?;
+        self.memory
+            .store_half(paddr, value)
+            .map_err(|e| at(e, addr))
+    }
+
+    pub(crate) fn store_word(&mut self, addr: u32, value: u32) -> Result<(), CPUError> {
+        let paddr = self.translate(addr, 4, Access::Store)?;
+        self.memory
+            .store_word(paddr, value)
+            .map_err(|e| at(e, addr))
+    }
+}
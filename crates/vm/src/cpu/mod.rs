@@ -1,59 +1,281 @@
 use crate::{
-    cpu::errors::CPUError,
-    decoder::decode,
+    cpu::{
+        csr::{CsrFile, Privilege},
+        errors::CPUError,
+        mmu::Mmu,
+        trap::{Exception, Interrupt, TrapMode},
+    },
+    decoder::{decode_compressed_with, decode_with, is_compressed},
+    isa::IsaConfig,
     itxs::Instruction,
     memory::{Memory, errors::MemoryError},
+    syscall::SyscallRegistry,
 };
 
-mod errors;
+pub(crate) mod alu;
+pub mod csr;
+pub mod errors;
 mod exec;
+pub mod mmu;
+pub mod pmp;
+#[cfg(test)]
 mod tests;
+pub mod trap;
 
-pub type StepResult = Instruction;
+/// Why [`CPU::run`] handed control back to the host.
+#[derive(Debug, PartialEq)]
+pub enum StopReason {
+    /// The guest called `exit`; carries its exit code.
+    Halt(i32),
+    Breakpoint,
+    /// The guest executed `wfi` with no enabled interrupt that could ever
+    /// wake it; the host has to raise one (e.g. through `msip`) first.
+    WaitForInterrupt,
+    InstructionLimit,
+    Fault(CPUError),
+}
 
-impl Default for CPU {
-    fn default() -> Self {
-        CPU {
-            regs: [0; 32],
-            pc: 0,
-            memory: Memory::default(),
-        }
-    }
+#[derive(Debug, PartialEq)]
+pub struct StepResult {
+    /// The instruction that ran, or `None` if the step trapped before one
+    /// could be fetched and decoded.
+    pub insn: Option<Instruction>,
+    pub stop: Option<StopReason>,
+    /// The exception taken instead of completing `insn`, if any.
+    pub trap: Option<Exception>,
+    /// The interrupt taken before any instruction ran, if any.
+    pub interrupt: Option<Interrupt>,
 }
 
+#[derive(Default)]
 pub struct CPU {
     regs: [u32; 32],
     pc: u32,
+    exit_code: Option<i32>,
+    /// Address reserved by the last LR.W, cleared by SC.W.
+    reservation: Option<u32>,
+    privilege: Privilege,
+    trap_mode: TrapMode,
+    mmu: Mmu,
+    isa: IsaConfig,
+    pub csrs: CsrFile,
     pub memory: Memory,
+    pub syscalls: SyscallRegistry,
 }
 
 impl CPU {
-    pub fn new(mem_size: usize) -> Result<Self, MemoryError> {
+    /// Creates a CPU with `mem_size` bytes of memory that only accepts the
+    /// instructions enabled in `isa`.
+    pub fn new(mem_size: usize, isa: IsaConfig) -> Result<Self, MemoryError> {
         Ok(CPU {
             regs: [0; 32],
             pc: 0,
+            exit_code: None,
+            reservation: None,
+            privilege: Privilege::Machine,
+            trap_mode: TrapMode::Host,
+            mmu: Mmu::default(),
+            isa,
+            csrs: CsrFile::new(),
             memory: Memory::new(mem_size)?,
+            syscalls: SyscallRegistry::default(),
         })
     }
 
+    pub fn isa(&self) -> IsaConfig {
+        self.isa
+    }
+
+    /// The privilege level the hart is running at. Harts start in M-mode.
+    pub fn privilege(&self) -> Privilege {
+        self.privilege
+    }
+
+    pub fn set_privilege(&mut self, privilege: Privilege) {
+        self.privilege = privilege;
+    }
+
+    pub fn trap_mode(&self) -> TrapMode {
+        self.trap_mode
+    }
+
+    /// Chooses whether faults go back to the host or to the guest's trap
+    /// handler. CPUs start in [`TrapMode::Host`].
+    pub fn set_trap_mode(&mut self, mode: TrapMode) {
+        self.trap_mode = mode;
+    }
+
+    /// Reads register `idx`. Like x0, registers the ISA doesn't have (x16-x31
+    /// under RV32E) read as zero.
     pub fn read_reg(&self, idx: usize) -> u32 {
-        if idx == 0 { 0 } else { self.regs[idx] }
+        if idx == 0 || idx >= self.isa.num_regs() {
+            0
+        } else {
+            self.regs[idx]
+        }
     }
 
+    /// Writes register `idx`. Writes to x0 and to registers the ISA doesn't
+    /// have are ignored.
     pub fn write_reg(&mut self, idx: usize, value: u32) {
-        if idx != 0 && idx < 32 {
+        if idx != 0 && idx < self.isa.num_regs() {
             self.regs[idx] = value;
         }
     }
 
+    pub fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u32) {
+        self.pc = pc;
+    }
+
+    /// The code passed to the `exit` syscall, once the guest has exited.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
     pub fn advance_pc(&mut self, offset: u32) {
         self.pc = self.pc.wrapping_add(offset);
     }
 
-    pub fn step(&mut self) -> Result<StepResult, CPUError> {
-        let word = self.memory.fetch(self.pc)?;
-        let insn = decode(word)?;
-        self.advance_pc(4);
-        Ok(insn)
+    /// Fetches, decodes and executes the instruction at `pc`. `pc` is only
+    /// moved once the instruction has executed, so a failed fetch or decode
+    /// leaves it pointing at the faulting instruction.
+    ///
+    /// In [`TrapMode::Guest`], faults become exceptions that vector through
+    /// `mtvec` instead of errors. `ebreak`, U-mode `ecall`s and M-mode
+    /// `ecall`s with no host syscall registered trap too, and pending
+    /// interrupts are taken between instructions.
+    ///
+    /// The CLINT's `mtime` advances once per step.
+    pub fn step_exec(&mut self) -> Result<StepResult, CPUError> {
+        self.sync_clint();
+        let res = self.step_inner();
+        self.memory.clint.tick();
+        self.sync_clint();
+        res
+    }
+
+    fn step_inner(&mut self) -> Result<StepResult, CPUError> {
+        if self.traps_to_guest()
+            && let Some(irq) = self.csrs.pending_interrupt(self.privilege)
+        {
+            self.pc = self.csrs.enter_interrupt(self.pc, irq, self.privilege);
+            self.privilege = Privilege::Machine;
+            return Ok(StepResult {
+                insn: None,
+                stop: None,
+                trap: None,
+                interrupt: Some(irq),
+            });
+        }
+        let word = match self.fetch(self.pc) {
+            Ok(word) => word,
+            Err(e) => {
+                let exc = Exception::from_fetch(&e);
+                return self.trap(e, exc, None);
+            }
+        };
+        let decoded = if is_compressed(word) {
+            decode_compressed_with(word as u16, self.isa).map(|insn| (insn, 2))
+        } else {
+            decode_with(word, self.isa).map(|insn| (insn, 4))
+        };
+        let (insn, len) = match decoded {
+            Ok(decoded) => decoded,
+            Err(e) => return self.trap(e.into(), Exception::IllegalInstruction(word), None),
+        };
+        if self.traps_to_guest() {
+            // U-mode ecalls belong to the guest kernel, not the host
+            let exc = match insn {
+                Instruction::EBREAK => Some(Exception::Breakpoint(self.pc)),
+                Instruction::ECALL if self.privilege == Privilege::User => {
+                    Some(Exception::EcallFromU)
+                }
+                _ => None,
+            };
+            if let Some(exc) = exc {
+                return Ok(self.take_trap(exc, Some(insn)));
+            }
+        }
+        match self.execute_sized(insn.clone(), len) {
+            Ok(stop) => Ok(StepResult {
+                insn: Some(insn),
+                stop,
+                trap: None,
+                interrupt: None,
+            }),
+            Err(e) => match Exception::from_error(&e, &insn, word) {
+                Some(exc) => self.trap(e, exc, Some(insn)),
+                None => Err(e),
+            },
+        }
+    }
+
+    fn traps_to_guest(&self) -> bool {
+        self.trap_mode == TrapMode::Guest
+    }
+
+    /// Vectors to the guest's trap handler for `exc`, or hands `err` back to
+    /// the host in [`TrapMode::Host`].
+    fn trap(
+        &mut self,
+        err: CPUError,
+        exc: Exception,
+        insn: Option<Instruction>,
+    ) -> Result<StepResult, CPUError> {
+        if !self.traps_to_guest() {
+            return Err(err);
+        }
+        Ok(self.take_trap(exc, insn))
+    }
+
+    fn take_trap(&mut self, exc: Exception, insn: Option<Instruction>) -> StepResult {
+        self.pc = self
+            .csrs
+            .enter_trap(self.pc, exc.cause(), exc.tval(), self.privilege);
+        self.privilege = Privilege::Machine;
+        StepResult {
+            insn,
+            stop: None,
+            trap: Some(exc),
+            interrupt: None,
+        }
+    }
+
+    fn sync_clint(&mut self) {
+        let clint = &self.memory.clint;
+        self.csrs.sync(clint.mtime(), clint.pending());
+    }
+
+    /// Runs until the guest exits, hits a breakpoint, faults, or `max_steps`
+    /// instructions have executed.
+    pub fn run(&mut self, max_steps: u64) -> StopReason {
+        run_steps(self.exit_code, max_steps, || self.step_exec())
+    }
+}
+
+/// The run loop [`CPU::run`] and [`CPU64::run`](crate::cpu64::CPU64::run)
+/// share: calls `step` until it stops or fails, at most `max_steps` times. A
+/// guest that already exited with `exit_code` does not run again.
+pub(crate) fn run_steps(
+    exit_code: Option<i32>,
+    max_steps: u64,
+    mut step: impl FnMut() -> Result<StepResult, CPUError>,
+) -> StopReason {
+    if let Some(code) = exit_code {
+        return StopReason::Halt(code);
+    }
+    for _ in 0..max_steps {
+        match step() {
+            Ok(StepResult {
+                stop: Some(reason), ..
+            }) => return reason,
+            Ok(_) => {}
+            Err(e) => return StopReason::Fault(e),
+        }
     }
+    StopReason::InstructionLimit
 }
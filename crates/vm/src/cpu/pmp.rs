@@ -0,0 +1,122 @@
+use crate::{
+    constants::PMP_ENTRIES,
+    cpu::{csr::Privilege, mmu::Access},
+};
+
+pub const PMP_R: u8 = 1 << 0;
+pub const PMP_W: u8 = 1 << 1;
+pub const PMP_X: u8 = 1 << 2;
+pub const PMP_A: u8 = 0x3 << 3;
+pub const PMP_L: u8 = 1 << 7;
+
+pub const PMP_OFF: u8 = 0 << 3;
+pub const PMP_TOR: u8 = 1 << 3;
+pub const PMP_NA4: u8 = 2 << 3;
+pub const PMP_NAPOT: u8 = 3 << 3;
+
+/// Physical memory protection: sixteen regions, each described by a
+/// configuration byte (packed four to a `pmpcfg` CSR) and a `pmpaddr` CSR
+/// holding bits 33:2 of an address.
+///
+/// Entries bind U-mode accesses, and M-mode accesses too once locked. U-mode
+/// accesses no entry matches are denied, so a U-mode guest needs at least
+/// one entry granting it memory.
+#[derive(Debug, Default)]
+pub struct Pmp {
+    cfg: [u8; PMP_ENTRIES],
+    addr: [u32; PMP_ENTRIES],
+}
+
+impl Pmp {
+    /// Reads `pmpcfg<index>`.
+    pub fn read_cfg(&self, index: usize) -> u32 {
+        let bytes = &self.cfg[index * 4..index * 4 + 4];
+        u32::from_le_bytes(bytes.try_into().unwrap())
+    }
+
+    /// Writes `pmpcfg<index>`, skipping locked entries. The reserved
+    /// write-without-read combination reads back as no access.
+    pub fn write_cfg(&mut self, index: usize, value: u32) {
+        for (i, byte) in value.to_le_bytes().into_iter().enumerate() {
+            let entry = index * 4 + i;
+            if self.cfg[entry] & PMP_L != 0 {
+                continue;
+            }
+            let mut cfg = byte & (PMP_L | PMP_A | PMP_X | PMP_W | PMP_R);
+            if cfg & PMP_R == 0 {
+                cfg &= !PMP_W;
+            }
+            self.cfg[entry] = cfg;
+        }
+    }
+
+    pub fn read_addr(&self, index: usize) -> u32 {
+        self.addr[index]
+    }
+
+    /// Writes `pmpaddr<index>` unless its entry is locked, or the next entry
+    /// is a locked TOR region using it as the bottom bound.
+    pub fn write_addr(&mut self, index: usize, value: u32) {
+        let locked = |i: usize| self.cfg.get(i).is_some_and(|cfg| cfg & PMP_L != 0);
+        let next_tor = self
+            .cfg
+            .get(index + 1)
+            .is_some_and(|cfg| cfg & PMP_A == PMP_TOR);
+        if locked(index) || (next_tor && locked(index + 1)) {
+            return;
+        }
+        self.addr[index] = value;
+    }
+
+    /// Whether `privilege` may perform `access` on the `len` bytes at the
+    /// physical address `addr`. The lowest-numbered entry overlapping the
+    /// access decides, and it must cover every byte.
+    pub fn allows(&self, addr: u32, len: u32, access: Access, privilege: Privilege) -> bool {
+        let start = addr as u64;
+        let end = start + len as u64;
+        for entry in 0..PMP_ENTRIES {
+            let Some((base, top)) = self.range(entry) else {
+                continue;
+            };
+            if end <= base || start >= top {
+                continue;
+            }
+            if start < base || end > top {
+                return false;
+            }
+            let cfg = self.cfg[entry];
+            if privilege == Privilege::Machine && cfg & PMP_L == 0 {
+                return true;
+            }
+            let needed = match access {
+                Access::Fetch => PMP_X,
+                Access::Load => PMP_R,
+                Access::Store => PMP_W,
+            };
+            return cfg & needed != 0;
+        }
+        privilege == Privilege::Machine
+    }
+
+    /// The half-open physical range matched by `entry`, if it is on.
+    fn range(&self, entry: usize) -> Option<(u64, u64)> {
+        let addr = self.addr[entry] as u64;
+        match self.cfg[entry] & PMP_A {
+            PMP_TOR => {
+                let base = match entry {
+                    0 => 0,
+                    _ => (self.addr[entry - 1] as u64) << 2,
+                };
+                Some((base, addr << 2))
+            }
+            PMP_NA4 => Some((addr << 2, (addr << 2) + 4)),
+            PMP_NAPOT => {
+                // trailing ones in pmpaddr encode a region of 2^(ones + 3) bytes
+                let size = 1u64 << (self.addr[entry].trailing_ones() + 3);
+                let base = (addr << 2) & !(size - 1);
+                Some((base, base + size))
+            }
+            _ => None,
+        }
+    }
+}
@@ -1,4 +1,16 @@
 use super::*;
+use crate::constants::{
+    CSR_CYCLE, CSR_INSTRET, CSR_MCYCLE, CSR_MEPC, CSR_MIE, CSR_MINSTRETH, CSR_MSTATUS, CSR_MTVEC,
+    CSR_PMPADDR0, CSR_PMPCFG0, CSR_SATP, CSR_TIME, REG_A0, REG_A7, REG_T0, SYS_EXIT, SYS_WRITE,
+};
+use crate::cpu::csr::{CsrFile, MSTATUS_MIE, MSTATUS_MPIE, MSTATUS_MPP, Privilege};
+use crate::cpu::mmu::{PTE_A, PTE_D, PTE_R, PTE_U, PTE_V, PTE_W, PTE_X, SATP_MODE_SV32};
+use crate::cpu::pmp::{PMP_L, PMP_NA4, PMP_NAPOT, PMP_R, PMP_TOR, PMP_W, PMP_X};
+use crate::cpu::trap::{Exception, Interrupt, TrapMode};
+use crate::isa::IsaConfig;
+use crate::memory::clint::{MIP_MSIP, MIP_MTIP};
+use crate::memory::errors::MemoryError;
+use crate::syscall::{STDOUT_FD, SharedBuffer, SyscallContext, Write};
 
 fn return_cpu() -> CPU {
     CPU::default()
@@ -22,48 +34,110 @@ fn test_read_write_regs() {
     assert_eq!(cpu.read_reg(0), 0);
 }
 
+fn load_program(cpu: &mut CPU, words: &[u32]) {
+    for (i, word) in words.iter().enumerate() {
+        cpu.memory.store_word(i as u32 * 4, *word).unwrap();
+    }
+}
+
 #[test]
 fn test_step_fetch_decode() {
     let mut cpu = return_cpu();
     cpu.memory.store_word(0, 0x0050_0113).unwrap();
-    let insn = cpu.step().unwrap();
+    let res = cpu.step_exec().unwrap();
     assert_eq!(
-        insn,
-        Instruction::OP_IMM {
+        res.insn,
+        Some(Instruction::ADDI {
             rd: 2,
             rs1: 0,
             imm: 5
-        }
+        })
     );
+    assert_eq!(res.stop, None);
     assert_eq!(cpu.pc, 4);
+    assert_eq!(cpu.read_reg(2), 5);
 }
 
 #[test]
 fn test_step_memory_error() {
-    let mut cpu = CPU::new(4).unwrap();
+    let mut cpu = CPU::new(4, IsaConfig::default()).unwrap();
     cpu.memory.store_word(0, 0x0000_0013).unwrap();
 
-    let insn = cpu.step().unwrap();
+    let res = cpu.step_exec().unwrap();
     assert_eq!(
-        insn,
-        Instruction::OP_IMM {
+        res.insn,
+        Some(Instruction::ADDI {
             rd: 0,
             rs1: 0,
             imm: 0
-        }
+        })
     );
     assert_eq!(cpu.pc, 4);
 
-    let err = cpu.step().unwrap_err();
+    let err = cpu.step_exec().unwrap_err();
     assert!(matches!(err, CPUError::Memory(_)));
+    assert_eq!(cpu.pc, 4);
 }
 
 #[test]
 fn test_step_decode_error() {
     let mut cpu = return_cpu();
     cpu.memory.store_byte(0, 0xFF).unwrap();
-    let err = cpu.step().unwrap_err();
-    matches!(err, CPUError::Decode(_));
+    let err = cpu.step_exec().unwrap_err();
+    assert!(matches!(err, CPUError::Decode(_)));
+    assert_eq!(cpu.pc, 0);
+}
+
+#[test]
+fn test_step_jal_links_next_instruction() {
+    let mut cpu = return_cpu();
+    // jal ra, 8
+    load_program(&mut cpu, &[0x0080_00EF]);
+    cpu.step_exec().unwrap();
+    assert_eq!(cpu.read_reg(1), 4);
+    assert_eq!(cpu.pc, 8);
+}
+
+#[test]
+fn test_run_until_halt() {
+    let mut cpu = return_cpu();
+    load_program(
+        &mut cpu,
+        &[
+            0x0050_0093, // addi x1, x0, 5
+            0x0011_0113, // addi x2, x2, 1
+            0xFE11_1EE3, // bne x2, x1, -4
+            0x0001_0513, // addi a0, x2, 0
+            0x05D0_0893, // addi a7, x0, 93
+            0x0000_0073, // ecall
+        ],
+    );
+    assert_eq!(cpu.run(100), StopReason::Halt(5));
+    assert_eq!(cpu.exit_code(), Some(5));
+    assert_eq!(cpu.pc, 24);
+
+    assert_eq!(cpu.run(100), StopReason::Halt(5));
+    assert_eq!(cpu.pc, 24);
+}
+
+#[test]
+fn test_run_stop_reasons() {
+    let mut cpu = return_cpu();
+    load_program(&mut cpu, &[0x0010_0073]);
+    assert_eq!(cpu.run(10), StopReason::Breakpoint);
+
+    let mut cpu = return_cpu();
+    // jal x0, 0
+    load_program(&mut cpu, &[0x0000_006F]);
+    assert_eq!(cpu.run(10), StopReason::InstructionLimit);
+    assert_eq!(cpu.pc, 0);
+
+    let mut cpu = return_cpu();
+    load_program(&mut cpu, &[0xFFFF_FFFF]);
+    assert!(matches!(
+        cpu.run(10),
+        StopReason::Fault(CPUError::Decode(_))
+    ));
 }
 
 #[test]
@@ -71,36 +145,148 @@ fn test_add_and_sub() {
     let mut cpu = return_cpu();
     cpu.write_reg(1, 10);
     cpu.write_reg(2, 3);
-    cpu.execute(Instruction::OP {
+    cpu.execute(Instruction::ADD {
         rd: 3,
         rs1: 1,
         rs2: 2,
-        funct7: 0x00,
     })
     .unwrap();
     assert_eq!(cpu.read_reg(3), 13);
-    cpu.execute(Instruction::OP {
+    cpu.execute(Instruction::SUB {
         rd: 4,
         rs1: 1,
         rs2: 2,
-        funct7: 0x20,
     })
     .unwrap();
     assert_eq!(cpu.read_reg(4), 7);
 }
 
+#[test]
+fn test_op_imm() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, -3i32 as u32);
+
+    cpu.execute(Instruction::SLTI {
+        rd: 2,
+        rs1: 1,
+        imm: -2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(2), 1);
+    cpu.execute(Instruction::SLTIU {
+        rd: 2,
+        rs1: 1,
+        imm: -2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(2), 1);
+    cpu.execute(Instruction::SLTIU {
+        rd: 2,
+        rs1: 1,
+        imm: 5,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(2), 0);
+
+    cpu.write_reg(1, 0x0000_0F0F);
+    cpu.execute(Instruction::XORI {
+        rd: 3,
+        rs1: 1,
+        imm: -1,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0xFFFF_F0F0);
+    cpu.execute(Instruction::ORI {
+        rd: 3,
+        rs1: 1,
+        imm: 0xF0,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x0000_0FFF);
+    cpu.execute(Instruction::ANDI {
+        rd: 3,
+        rs1: 1,
+        imm: 0xFF,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x0000_000F);
+}
+
+#[test]
+fn test_register_alu() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, 0x8000_00F0);
+    cpu.write_reg(2, 0x24);
+    cpu.execute(Instruction::SLL {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x0000_0F00);
+    cpu.execute(Instruction::SRL {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x0800_000F);
+    cpu.execute(Instruction::SRA {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0xF800_000F);
+    cpu.execute(Instruction::SLT {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 1);
+    cpu.execute(Instruction::SLTU {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0);
+    cpu.execute(Instruction::XOR {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x8000_00D4);
+    cpu.execute(Instruction::OR {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x8000_00F4);
+    cpu.execute(Instruction::AND {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x0000_0020);
+}
+
 #[test]
 fn test_load_store() {
     let mut cpu = return_cpu();
     cpu.write_reg(1, 100);
     cpu.write_reg(2, 0xDEADBEEF);
-    cpu.execute(Instruction::STORE {
+    cpu.execute(Instruction::SW {
         rs1: 1,
         rs2: 2,
         imm: 0,
     })
     .unwrap();
-    cpu.execute(Instruction::LOAD {
+    cpu.execute(Instruction::LW {
         rd: 3,
         rs1: 1,
         imm: 0,
@@ -109,6 +295,70 @@ fn test_load_store() {
     assert_eq!(cpu.read_reg(3), 0xDEADBEEF);
 }
 
+#[test]
+fn test_sub_word_load_store() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, 100);
+    cpu.write_reg(2, 0x1234_8281);
+    cpu.execute(Instruction::SW {
+        rs1: 1,
+        rs2: 0,
+        imm: 0,
+    })
+    .unwrap();
+    cpu.execute(Instruction::SH {
+        rs1: 1,
+        rs2: 2,
+        imm: 2,
+    })
+    .unwrap();
+    cpu.execute(Instruction::SB {
+        rs1: 1,
+        rs2: 2,
+        imm: 0,
+    })
+    .unwrap();
+    assert_eq!(cpu.memory.load_word(100).unwrap(), 0x8281_0081);
+
+    cpu.execute(Instruction::LB {
+        rd: 3,
+        rs1: 1,
+        imm: 0,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0xFFFF_FF81);
+    cpu.execute(Instruction::LBU {
+        rd: 3,
+        rs1: 1,
+        imm: 0,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x0000_0081);
+    cpu.execute(Instruction::LH {
+        rd: 3,
+        rs1: 1,
+        imm: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0xFFFF_8281);
+    cpu.execute(Instruction::LHU {
+        rd: 3,
+        rs1: 1,
+        imm: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x0000_8281);
+
+    let err = cpu
+        .execute(Instruction::LH {
+            rd: 3,
+            rs1: 1,
+            imm: 1,
+        })
+        .unwrap_err();
+    assert!(matches!(err, CPUError::Memory(_)));
+}
+
 #[test]
 fn test_mul_div_rem() {
     let mut cpu = return_cpu();
@@ -151,6 +401,51 @@ fn test_mul_div_rem() {
     assert_eq!(cpu.read_reg(7), 3);
 }
 
+#[test]
+fn test_mul_high() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, -2i32 as u32);
+    cpu.write_reg(2, 0x8000_0000);
+    cpu.execute(Instruction::MULH {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 1);
+    cpu.execute(Instruction::MULHSU {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0xFFFF_FFFF);
+    cpu.execute(Instruction::MULHU {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0x7FFF_FFFF);
+
+    cpu.write_reg(1, u32::MAX);
+    cpu.write_reg(2, u32::MAX);
+    cpu.execute(Instruction::MULHU {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0xFFFF_FFFE);
+    cpu.execute(Instruction::MULH {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0);
+}
+
 #[test]
 fn test_auipc_jal_jalr() {
     let mut cpu = return_cpu();
@@ -180,23 +475,36 @@ fn test_auipc_jal_jalr() {
 fn test_shift_imm() {
     let mut cpu = return_cpu();
     cpu.write_reg(1, 1);
-    cpu.execute(Instruction::SHIFT_IMM {
+    cpu.execute(Instruction::SLLI {
         rd: 2,
         rs1: 1,
         shamt: 3,
-        is_srai: false,
     })
     .unwrap();
     assert_eq!(cpu.read_reg(2), 8);
     cpu.write_reg(1, u32::MAX);
-    cpu.execute(Instruction::SHIFT_IMM {
+    cpu.execute(Instruction::SRLI {
         rd: 2,
         rs1: 1,
         shamt: 1,
-        is_srai: true,
     })
     .unwrap();
     assert_eq!(cpu.read_reg(2), u32::MAX >> 1);
+    cpu.execute(Instruction::SRAI {
+        rd: 2,
+        rs1: 1,
+        shamt: 1,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(2), u32::MAX);
+    cpu.write_reg(1, 0x8000_0000);
+    cpu.execute(Instruction::SRAI {
+        rd: 2,
+        rs1: 1,
+        shamt: 31,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(2), u32::MAX);
 }
 
 #[test]
@@ -205,7 +513,7 @@ fn test_branch_equal() {
     cpu.pc = 10;
     cpu.write_reg(1, 5);
     cpu.write_reg(2, 5);
-    cpu.execute(Instruction::BRANCH {
+    cpu.execute(Instruction::BEQ {
         rs1: 1,
         rs2: 2,
         imm: 8,
@@ -214,21 +522,1262 @@ fn test_branch_equal() {
     assert_eq!(cpu.pc, 18);
     cpu.pc = 10;
     cpu.write_reg(2, 6);
-    cpu.execute(Instruction::BRANCH {
+    cpu.execute(Instruction::BEQ {
         rs1: 1,
         rs2: 2,
         imm: 8,
     })
     .unwrap();
-    assert_eq!(cpu.pc, 10);
+    assert_eq!(cpu.pc, 14);
+}
+
+#[test]
+fn test_branch_conditions() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, -1i32 as u32);
+    cpu.write_reg(2, 1);
+
+    let cases = [
+        (
+            Instruction::BNE {
+                rs1: 1,
+                rs2: 2,
+                imm: 8,
+            },
+            24,
+        ),
+        (
+            Instruction::BNE {
+                rs1: 2,
+                rs2: 2,
+                imm: 8,
+            },
+            20,
+        ),
+        (
+            Instruction::BLT {
+                rs1: 1,
+                rs2: 2,
+                imm: 8,
+            },
+            24,
+        ),
+        (
+            Instruction::BLT {
+                rs1: 2,
+                rs2: 1,
+                imm: 8,
+            },
+            20,
+        ),
+        (
+            Instruction::BGE {
+                rs1: 1,
+                rs2: 2,
+                imm: 8,
+            },
+            20,
+        ),
+        (
+            Instruction::BGE {
+                rs1: 2,
+                rs2: 2,
+                imm: -8,
+            },
+            8,
+        ),
+        (
+            Instruction::BLTU {
+                rs1: 1,
+                rs2: 2,
+                imm: 8,
+            },
+            20,
+        ),
+        (
+            Instruction::BLTU {
+                rs1: 2,
+                rs2: 1,
+                imm: 8,
+            },
+            24,
+        ),
+        (
+            Instruction::BGEU {
+                rs1: 1,
+                rs2: 2,
+                imm: 8,
+            },
+            24,
+        ),
+        (
+            Instruction::BGEU {
+                rs1: 2,
+                rs2: 1,
+                imm: 8,
+            },
+            20,
+        ),
+    ];
+    for (insn, expected_pc) in cases {
+        cpu.pc = 16;
+        cpu.execute(insn.clone()).unwrap();
+        assert_eq!(cpu.pc, expected_pc, "{:?}", insn);
+    }
 }
 
 #[test]
 fn test_ecall_ebreak() {
     let mut cpu = return_cpu();
     cpu.pc = 30;
+    cpu.write_reg(REG_A7, SYS_EXIT);
+    cpu.write_reg(REG_A0, -1i32 as u32);
+    let stop = cpu.execute(Instruction::ECALL).unwrap();
+    assert_eq!(stop, Some(StopReason::Halt(-1)));
+    assert_eq!(cpu.exit_code(), Some(-1));
+    assert_eq!(cpu.pc, 34);
+    let stop = cpu.execute(Instruction::EBREAK).unwrap();
+    assert_eq!(stop, Some(StopReason::Breakpoint));
+    assert_eq!(cpu.pc, 38);
+}
+
+#[test]
+fn test_ecall_unknown_syscall() {
+    let mut cpu = return_cpu();
+    cpu.pc = 8;
+    cpu.write_reg(REG_A7, 0xFFFF);
+    let err = cpu.execute(Instruction::ECALL).unwrap_err();
+    assert_eq!(err, CPUError::UnknownSyscall(0xFFFF));
+    assert_eq!(cpu.pc, 8);
+    assert_eq!(cpu.exit_code(), None);
+}
+
+#[test]
+fn test_ecall_custom_handler() {
+    let mut cpu = return_cpu();
+    cpu.syscalls.register(0x100, |ctx: &mut SyscallContext| {
+        let addr = ctx.arg(0);
+        let word = ctx.memory.load_word(addr)?;
+        ctx.memory.store_word(addr, word + ctx.arg(1))?;
+        ctx.set_return(word);
+        Ok(None)
+    });
+    cpu.memory.store_word(64, 40).unwrap();
+    cpu.write_reg(REG_A7, 0x100);
+    cpu.write_reg(REG_A0, 64);
+    cpu.write_reg(REG_A0 + 1, 2);
+
+    assert_eq!(cpu.execute(Instruction::ECALL).unwrap(), None);
+    assert_eq!(cpu.read_reg(REG_A0), 40);
+    assert_eq!(cpu.memory.load_word(64).unwrap(), 42);
+    assert_eq!(cpu.pc, 4);
+
+    cpu.write_reg(REG_A0, 1024);
+    let err = cpu.execute(Instruction::ECALL).unwrap_err();
+    assert!(matches!(err, CPUError::Memory(_)));
+    assert_eq!(cpu.pc, 4);
+}
+
+#[test]
+fn test_run_guest_write() {
+    let mut cpu = return_cpu();
+    let out = SharedBuffer::new();
+    cpu.syscalls
+        .register(SYS_WRITE, Write::new().with_sink(STDOUT_FD, out.clone()));
+    for (i, b) in b"hi".iter().enumerate() {
+        cpu.memory.store_byte(64 + i as u32, *b).unwrap();
+    }
+    load_program(
+        &mut cpu,
+        &[
+            0x0010_0513, // addi a0, x0, 1
+            0x0400_0593, // addi a1, x0, 64
+            0x0020_0613, // addi a2, x0, 2
+            0x0400_0893, // addi a7, x0, 64
+            0x0000_0073, // ecall
+            0x05D0_0893, // addi a7, x0, 93
+            0x0000_0073, // ecall
+        ],
+    );
+    assert_eq!(cpu.run(100), StopReason::Halt(2));
+    assert_eq!(out.contents(), b"hi");
+}
+
+#[test]
+fn test_run_compressed() {
+    let mut cpu = return_cpu();
+    let program: [u16; 8] = [
+        0x4515, // c.li a0, 5
+        0x2011, // c.jal 4
+        0xA019, // c.j 6
+        0x0505, // c.addi a0, 1
+        0x8082, // c.jr ra
+        0x0893, // addi a7, x0, 93 (low half)
+        0x05D0, // addi a7, x0, 93 (high half)
+        0x0073, // ecall (low half)
+    ];
+    for (i, half) in program.iter().enumerate() {
+        cpu.memory.store_half(i as u32 * 2, *half).unwrap();
+    }
+    assert_eq!(cpu.run(100), StopReason::Halt(6));
+    assert_eq!(cpu.read_reg(1), 4);
+    assert_eq!(cpu.pc, 18);
+}
+
+#[test]
+fn test_counters_track_retired_instructions() {
+    let mut cpu = return_cpu();
+    load_program(
+        &mut cpu,
+        &[
+            0x0000_0013, // nop
+            0x0000_0013, // nop
+            0xC000_2573, // rdcycle a0
+            0xC020_25F3, // rdinstret a1
+            0x0000_0073, // ecall
+        ],
+    );
+    cpu.write_reg(REG_A7, SYS_EXIT);
+    assert_eq!(cpu.run(100), StopReason::Halt(2));
+    assert_eq!(cpu.read_reg(11), 3);
+    assert_eq!(cpu.csrs.cycle(), 5);
+    assert_eq!(cpu.csrs.instret(), 5);
+    assert_eq!(cpu.csrs.read(CSR_TIME).unwrap(), 5);
+}
+
+#[test]
+fn test_csr_instructions() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, 0xF0);
+    cpu.execute(Instruction::CSRRW {
+        rd: 2,
+        rs1: 1,
+        csr: CSR_MCYCLE,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(2), 0);
+    assert_eq!(cpu.csrs.cycle(), 0xF0);
+    assert_eq!(cpu.csrs.instret(), 1);
+
+    cpu.execute(Instruction::CSRRSI {
+        rd: 0,
+        uimm: 0x1,
+        csr: CSR_MINSTRETH,
+    })
+    .unwrap();
+    assert_eq!(cpu.csrs.instret(), (1 << 32) + 1);
+    assert_eq!(cpu.csrs.cycle(), 0xF1);
+
+    cpu.write_reg(1, 0x0F);
+    cpu.execute(Instruction::CSRRC {
+        rd: 3,
+        rs1: 1,
+        csr: CSR_MCYCLE,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0xF1);
+    assert_eq!(cpu.csrs.cycle(), 0xF0);
+}
+
+#[test]
+fn test_csr_read_only_and_unknown() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, 5);
+
+    cpu.execute(Instruction::CSRRS {
+        rd: 2,
+        rs1: 0,
+        csr: CSR_INSTRET,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(2), 0);
+
+    let err = cpu
+        .execute(Instruction::CSRRW {
+            rd: 2,
+            rs1: 1,
+            csr: CSR_CYCLE,
+        })
+        .unwrap_err();
+    assert_eq!(err, CPUError::InvalidCsr(CSR_CYCLE));
+
+    let err = cpu
+        .execute(Instruction::CSRRS {
+            rd: 2,
+            rs1: 0,
+            csr: 0x7FF,
+        })
+        .unwrap_err();
+    assert_eq!(err, CPUError::InvalidCsr(0x7FF));
+    assert_eq!(cpu.pc, 4);
+    assert_eq!(cpu.csrs.instret(), 1);
+}
+
+#[test]
+fn test_lr_sc() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, 64);
+    cpu.write_reg(2, 7);
+    cpu.memory.store_word(64, 3).unwrap();
+
+    let sc = Instruction::SC_W {
+        rd: 3,
+        rs1: 1,
+        rs2: 2,
+        aq: false,
+        rl: false,
+    };
+    cpu.execute(sc.clone()).unwrap();
+    assert_eq!(cpu.read_reg(3), 1);
+    assert_eq!(cpu.memory.load_word(64).unwrap(), 3);
+
+    cpu.execute(Instruction::LR_W {
+        rd: 4,
+        rs1: 1,
+        aq: false,
+        rl: false,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(4), 3);
+    cpu.execute(sc.clone()).unwrap();
+    assert_eq!(cpu.read_reg(3), 0);
+    assert_eq!(cpu.memory.load_word(64).unwrap(), 7);
+
+    cpu.write_reg(2, 9);
+    cpu.execute(sc).unwrap();
+    assert_eq!(cpu.read_reg(3), 1);
+    assert_eq!(cpu.memory.load_word(64).unwrap(), 7);
+}
+
+#[test]
+fn test_amo() {
+    let mut cpu = return_cpu();
+    cpu.write_reg(1, 64);
+    cpu.write_reg(2, -2i32 as u32);
+
+    let cases = [
+        (
+            Instruction::AMOSWAP_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            0xFFFF_FFFE,
+        ),
+        (
+            Instruction::AMOADD_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            3,
+        ),
+        (
+            Instruction::AMOXOR_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            0xFFFF_FFFB,
+        ),
+        (
+            Instruction::AMOAND_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            4,
+        ),
+        (
+            Instruction::AMOOR_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            0xFFFF_FFFF,
+        ),
+        (
+            Instruction::AMOMIN_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            0xFFFF_FFFE,
+        ),
+        (
+            Instruction::AMOMAX_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            5,
+        ),
+        (
+            Instruction::AMOMINU_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            5,
+        ),
+        (
+            Instruction::AMOMAXU_W {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+                aq: false,
+                rl: false,
+            },
+            0xFFFF_FFFE,
+        ),
+    ];
+    for (insn, expected) in cases {
+        cpu.memory.store_word(64, 5).unwrap();
+        cpu.execute(insn.clone()).unwrap();
+        assert_eq!(cpu.read_reg(3), 5, "{:?}", insn);
+        assert_eq!(cpu.memory.load_word(64).unwrap(), expected, "{:?}", insn);
+    }
+
+    cpu.write_reg(1, 66);
+    let err = cpu
+        .execute(Instruction::AMOADD_W {
+            rd: 3,
+            rs1: 1,
+            rs2: 2,
+            aq: false,
+            rl: false,
+        })
+        .unwrap_err();
+    assert!(matches!(err, CPUError::Memory(_)));
+}
+
+#[test]
+fn test_fences_are_no_ops() {
+    let mut cpu = return_cpu();
+    load_program(
+        &mut cpu,
+        &[
+            0x0FF0_000F, // fence
+            0x0000_100F, // fence.i
+            0x0020_0513, // addi a0, x0, 2
+            0x05D0_0893, // addi a7, x0, 93
+            0x0000_0073, // ecall
+        ],
+    );
+    assert_eq!(cpu.run(100), StopReason::Halt(2));
+    assert_eq!(cpu.csrs.instret(), 5);
+}
+
+#[test]
+fn test_bitmanip_register_ops() {
+    use Instruction::*;
+    let (rd, rs1, rs2) = (3, 1, 2);
+    let a = 0x8000_00F0;
+    let b = 0x0000_0024;
+    let cases = [
+        (SH1ADD { rd, rs1, rs2 }, 0x0000_0204),
+        (SH2ADD { rd, rs1, rs2 }, 0x0000_03E4),
+        (SH3ADD { rd, rs1, rs2 }, 0x0000_07A4),
+        (ANDN { rd, rs1, rs2 }, 0x8000_00D0),
+        (ORN { rd, rs1, rs2 }, 0xFFFF_FFFB),
+        (XNOR { rd, rs1, rs2 }, 0x7FFF_FF2B),
+        (MIN { rd, rs1, rs2 }, a),
+        (MINU { rd, rs1, rs2 }, b),
+        (MAX { rd, rs1, rs2 }, b),
+        (MAXU { rd, rs1, rs2 }, a),
+        (ROL { rd, rs1, rs2 }, 0x0000_0F08),
+        (ROR { rd, rs1, rs2 }, 0x0800_000F),
+        (BCLR { rd, rs1, rs2 }, 0x8000_00E0),
+        (BEXT { rd, rs1, rs2 }, 1),
+        (BINV { rd, rs1, rs2 }, 0x8000_00E0),
+        (BSET { rd, rs1, rs2 }, a),
+    ];
+    for (instr, expected) in cases {
+        let mut cpu = return_cpu();
+        cpu.write_reg(1, a);
+        cpu.write_reg(2, b);
+        cpu.execute(instr.clone()).unwrap();
+        assert_eq!(cpu.read_reg(3), expected, "{instr:?}");
+    }
+}
+
+#[test]
+fn test_bitmanip_unary_ops() {
+    use Instruction::*;
+    let (rd, rs1) = (3, 1);
+    let v = 0x0012_8000;
+    let cases = [
+        (CLZ { rd, rs1 }, 11),
+        (CTZ { rd, rs1 }, 15),
+        (CPOP { rd, rs1 }, 3),
+        (SEXT_B { rd, rs1 }, 0),
+        (SEXT_H { rd, rs1 }, 0xFFFF_8000),
+        (ZEXT_H { rd, rs1 }, 0x0000_8000),
+        (ORC_B { rd, rs1 }, 0x00FF_FF00),
+        (REV8 { rd, rs1 }, 0x0080_1200),
+        (RORI { rd, rs1, shamt: 16 }, 0x8000_0012),
+        (BCLRI { rd, rs1, shamt: 15 }, 0x0012_0000),
+        (BEXTI { rd, rs1, shamt: 20 }, 1),
+        (BINVI { rd, rs1, shamt: 0 }, 0x0012_8001),
+        (BSETI { rd, rs1, shamt: 31 }, 0x8012_8000),
+    ];
+    for (instr, expected) in cases {
+        let mut cpu = return_cpu();
+        cpu.write_reg(1, v);
+        cpu.execute(instr.clone()).unwrap();
+        assert_eq!(cpu.read_reg(3), expected, "{instr:?}");
+    }
+    let mut cpu = return_cpu();
+    cpu.execute(CLZ { rd, rs1 }).unwrap();
+    assert_eq!(cpu.read_reg(3), 32);
+}
+
+#[test]
+fn test_bitmanip_disabled_by_isa() {
+    let mut cpu = CPU::new(4, IsaConfig::rv32im()).unwrap();
+    load_program(&mut cpu, &[0x20C5_A533]); // sh1add a0, a1, a2
+    assert_eq!(
+        cpu.step_exec().unwrap_err(),
+        CPUError::Decode(crate::decoder::errors::DecodeError::ExtensionDisabled(
+            0x20C5_A533
+        ))
+    );
+}
+
+#[test]
+fn test_compressed_rejected_without_c() {
+    let mut cpu = CPU::new(4, IsaConfig::rv32im()).unwrap();
+    cpu.memory.store_half(0, 0x4505).unwrap(); // c.li a0, 1
+    assert_eq!(
+        cpu.step_exec().unwrap_err(),
+        CPUError::Decode(crate::decoder::errors::DecodeError::ExtensionDisabled(
+            0x4505
+        ))
+    );
+    assert_eq!(cpu.pc(), 0);
+
+    let mut cpu = CPU::new(4, IsaConfig::default()).unwrap();
+    cpu.memory.store_half(0, 0x4505).unwrap();
+    cpu.step_exec().unwrap();
+    assert_eq!(cpu.read_reg(10), 1);
+}
+
+#[test]
+fn test_rv32e_rejects_upper_registers() {
+    let isa = IsaConfig {
+        e: true,
+        ..IsaConfig::default()
+    };
+    let mut cpu = CPU::new(64, isa).unwrap();
+    for (instr, reg) in [
+        (
+            Instruction::ADD {
+                rd: 16,
+                rs1: 1,
+                rs2: 2,
+            },
+            16,
+        ),
+        (
+            Instruction::ADDI {
+                rd: 1,
+                rs1: 31,
+                imm: 1,
+            },
+            31,
+        ),
+        (
+            Instruction::SW {
+                rs1: 2,
+                rs2: 17,
+                imm: 0,
+            },
+            17,
+        ),
+    ] {
+        assert_eq!(
+            cpu.execute(instr).unwrap_err(),
+            CPUError::InvalidRegister(reg)
+        );
+    }
+    assert_eq!(cpu.pc(), 0);
+    assert_eq!(cpu.csrs.instret(), 0);
+
+    // a decoded instruction naming a7 faults before touching the register file
+    load_program(&mut cpu, &[0x05D0_0893]); // addi a7, x0, 93
+    assert_eq!(cpu.step_exec().unwrap_err(), CPUError::InvalidRegister(17));
+    assert_eq!(cpu.read_reg(17), 0);
+
+    // nor can the host reach past x15
+    cpu.write_reg(16, 5);
+    assert_eq!(cpu.read_reg(16), 0);
+    cpu.write_reg(15, 5);
+    assert_eq!(cpu.read_reg(15), 5);
+}
+
+#[test]
+fn test_rv32e_syscall_number_in_t0() {
+    let isa = IsaConfig {
+        e: true,
+        ..IsaConfig::default()
+    };
+    let mut cpu = CPU::new(64, isa).unwrap();
+    load_program(
+        &mut cpu,
+        &[
+            0x0070_0513, // addi a0, x0, 7
+            0x05D0_0293, // addi t0, x0, 93
+            0x0000_0073, // ecall
+        ],
+    );
+    assert_eq!(cpu.run(10), StopReason::Halt(7));
+}
+
+#[test]
+fn test_rv32e_syscall_context_hides_upper_registers() {
+    let isa = IsaConfig {
+        e: true,
+        ..IsaConfig::default()
+    };
+    let mut cpu = CPU::new(64, isa).unwrap();
+    cpu.syscalls.register(1, |ctx: &mut SyscallContext| {
+        ctx.write_reg(16, 9);
+        ctx.set_return(ctx.arg(6) + ctx.read_reg(16));
+        Ok(None)
+    });
+    cpu.write_reg(REG_T0, 1);
     cpu.execute(Instruction::ECALL).unwrap();
-    assert_eq!(cpu.pc, 30);
-    cpu.execute(Instruction::EBREAK).unwrap();
-    assert_eq!(cpu.pc, 30);
+    assert_eq!(cpu.read_reg(REG_A0), 0);
+}
+
+#[test]
+fn test_rv64_only_instruction_unsupported() {
+    let mut cpu = return_cpu();
+    let instr = Instruction::ADDW {
+        rd: 1,
+        rs1: 2,
+        rs2: 3,
+    };
+    assert_eq!(
+        cpu.execute(instr.clone()).unwrap_err(),
+        CPUError::UnsupportedInstruction(instr)
+    );
+    assert_eq!(cpu.pc(), 0);
+}
+
+/// Installs a handler at 0x40 that skips the trapping instruction.
+fn install_skip_handler(cpu: &mut CPU) {
+    let handler = [
+        0x3410_2373, // csrr t1, mepc
+        0x0043_0313, // addi t1, t1, 4
+        0x3413_1073, // csrw mepc, t1
+        0x3020_0073, // mret
+    ];
+    for (i, word) in handler.iter().enumerate() {
+        cpu.memory.store_word(0x40 + i as u32 * 4, *word).unwrap();
+    }
+}
+
+#[test]
+fn test_trap_handler_and_mret() {
+    let mut cpu = return_cpu();
+    cpu.set_trap_mode(TrapMode::Guest);
+    load_program(
+        &mut cpu,
+        &[
+            0x0400_0293, // addi t0, x0, 0x40
+            0x3052_9073, // csrw mtvec, t0
+            0x0020_2503, // lw a0, 2(x0)
+            0x0070_0513, // addi a0, x0, 7
+            0x05D0_0893, // addi a7, x0, 93
+            0x0000_0073, // ecall
+        ],
+    );
+    install_skip_handler(&mut cpu);
+    assert_eq!(cpu.run(100), StopReason::Halt(7));
+    assert_eq!(cpu.csrs.mcause(), 4);
+    assert_eq!(cpu.csrs.mtval(), 2);
+    assert_eq!(cpu.csrs.mepc(), 12);
+}
+
+#[test]
+fn test_trap_causes() {
+    let cases = [
+        (0xFFFF_FFFF, Exception::IllegalInstruction(0xFFFF_FFFF)),
+        (0x7C00_2573, Exception::IllegalInstruction(0x7C00_2573)), // csrr a0, 0x7c0
+        (0x0010_0073, Exception::Breakpoint(8)),
+        (0x0000_0073, Exception::EcallFromM), // no host syscall 1
+        (0x0020_2503, Exception::LoadMisaligned(2)),
+        (0x7F00_2503, Exception::LoadAccessFault(0x7F0)),
+        (0x00A0_20A3, Exception::StoreMisaligned(1)), // sw a0, 1(x0)
+        (0x0000_0000, Exception::IllegalInstruction(0)), // c.unimp
+    ];
+    for (word, exc) in cases {
+        let mut cpu = return_cpu();
+        cpu.set_trap_mode(TrapMode::Guest);
+        load_program(&mut cpu, &[0x0400_0293, 0x3052_9073, word]);
+        cpu.write_reg(REG_A7, 1);
+        cpu.step_exec().unwrap();
+        cpu.step_exec().unwrap();
+        let instret = cpu.csrs.instret();
+
+        let res = cpu.step_exec().unwrap();
+        assert_eq!(res.trap, Some(exc), "{word:#010x}");
+        assert_eq!(res.stop, None);
+        assert_eq!(cpu.pc(), 0x40);
+        assert_eq!(cpu.csrs.mepc(), 8);
+        assert_eq!(cpu.csrs.mcause(), exc.cause());
+        assert_eq!(cpu.csrs.mtval(), exc.tval());
+        assert_eq!(cpu.csrs.instret(), instret);
+    }
+}
+
+#[test]
+fn test_faults_without_handler_are_errors() {
+    let mut cpu = return_cpu();
+    load_program(&mut cpu, &[0x0010_0073, 0x0020_2503]);
+    assert_eq!(cpu.run(10), StopReason::Breakpoint);
+    assert_eq!(
+        cpu.run(10),
+        StopReason::Fault(CPUError::Memory(MemoryError::UnalignedAccess {
+            addr: 2,
+            align: 4
+        }))
+    );
+    assert_eq!(cpu.pc(), 4);
+
+    // a handler address alone does not hand faults to the guest
+    cpu.csrs.write(CSR_MTVEC, 0x40).unwrap();
+    assert_eq!(cpu.trap_mode(), TrapMode::Host);
+    assert!(matches!(cpu.run(10), StopReason::Fault(_)));
+    assert_eq!(cpu.pc(), 4);
+
+    // while in guest mode even mtvec = 0 is a handler address
+    let mut cpu = return_cpu();
+    cpu.set_trap_mode(TrapMode::Guest);
+    load_program(&mut cpu, &[0x0000_0013, 0x0010_0073]);
+    cpu.step_exec().unwrap();
+    let res = cpu.step_exec().unwrap();
+    assert_eq!(res.trap, Some(Exception::Breakpoint(4)));
+    assert_eq!(cpu.pc(), 0);
+}
+
+#[test]
+fn test_misaligned_jump_target() {
+    let jal = Instruction::JAL { rd: 1, imm: 6 };
+    let mut cpu = CPU::new(128, IsaConfig::rv32im()).unwrap();
+    assert_eq!(
+        cpu.execute(jal.clone()).unwrap_err(),
+        CPUError::MisalignedTarget(6)
+    );
+    assert_eq!(cpu.read_reg(1), 0);
+    assert_eq!(cpu.pc(), 0);
+
+    // two-byte alignment is enough once compressed instructions exist
+    let mut cpu = return_cpu();
+    cpu.execute(jal).unwrap();
+    assert_eq!(cpu.pc(), 6);
+    assert_eq!(cpu.read_reg(1), 4);
+}
+
+#[test]
+fn test_mtvec_mode_and_mepc_alignment() {
+    let mut cpu = return_cpu();
+    cpu.csrs.write(CSR_MTVEC, 0x103).unwrap();
+    assert_eq!(cpu.csrs.mtvec(), 0x100);
+    cpu.csrs.write(CSR_MTVEC, 0x101).unwrap();
+    assert_eq!(cpu.csrs.mtvec(), 0x101);
+    cpu.csrs.write(CSR_MEPC, 0x203).unwrap();
+    assert_eq!(cpu.csrs.read(CSR_MEPC).unwrap(), 0x202);
+}
+
+#[test]
+fn test_user_mode_ecall_never_reaches_host() {
+    let mut cpu = sv32_cpu();
+    let out = SharedBuffer::new();
+    cpu.syscalls
+        .register(SYS_WRITE, Write::new().with_sink(STDOUT_FD, out.clone()));
+    cpu.memory.store_word(0x3000, 0x0000_0073).unwrap(); // ecall
+    cpu.memory.store_word(0x4000, 0xCAFE).unwrap();
+    // write(1, 0x1000, 4) names the user data page, but physical 0x1000 is
+    // the root page table
+    cpu.write_reg(REG_A7, SYS_WRITE);
+    cpu.write_reg(REG_A0, 1);
+    cpu.write_reg(11, 0x1000);
+    cpu.write_reg(12, 4);
+    assert_eq!(cpu.step_exec(), Err(CPUError::UserEcall));
+    assert_eq!(cpu.pc(), 0);
+    assert!(out.contents().is_empty());
+}
+
+#[test]
+fn test_user_mode_ecall_traps_to_kernel() {
+    let mut cpu = CPU::new(256, IsaConfig::default()).unwrap();
+    cpu.set_trap_mode(TrapMode::Guest);
+    grant_user_memory(&mut cpu);
+    load_program(
+        &mut cpu,
+        &[
+            0x0400_0293, // addi t0, x0, 0x40
+            0x3052_9073, // csrw mtvec, t0
+            0x0200_0293, // addi t0, x0, 0x20
+            0x3412_9073, // csrw mepc, t0
+            0x3020_0073, // mret
+        ],
+    );
+    // user code at 0x20
+    cpu.memory.store_word(0x20, 0x0050_0513).unwrap(); // addi a0, x0, 5
+    cpu.memory.store_word(0x24, 0x0000_0073).unwrap(); // ecall
+    // kernel trap handler at 0x40
+    cpu.memory.store_word(0x40, 0x3420_25F3).unwrap(); // csrr a1, mcause
+    cpu.memory.store_word(0x44, 0x05D0_0893).unwrap(); // addi a7, x0, 93
+    cpu.memory.store_word(0x48, 0x0000_0073).unwrap(); // ecall
+
+    for _ in 0..5 {
+        cpu.step_exec().unwrap();
+    }
+    assert_eq!(cpu.privilege(), Privilege::User);
+    assert_eq!(cpu.pc(), 0x20);
+
+    assert_eq!(cpu.run(100), StopReason::Halt(5));
+    assert_eq!(cpu.read_reg(11), 8);
+    assert_eq!(cpu.privilege(), Privilege::Machine);
+    assert_eq!(cpu.csrs.mepc(), 0x24);
+    assert_eq!(cpu.csrs.mstatus() & MSTATUS_MPP, 0);
+}
+
+#[test]
+fn test_user_mode_csr_and_mret_checks() {
+    let mut cpu = return_cpu();
+    cpu.set_privilege(Privilege::User);
+    let read_mstatus = Instruction::CSRRS {
+        rd: 10,
+        rs1: 0,
+        csr: CSR_MSTATUS,
+    };
+    assert_eq!(
+        cpu.execute(read_mstatus).unwrap_err(),
+        CPUError::InvalidCsr(CSR_MSTATUS)
+    );
+    assert_eq!(
+        cpu.execute(Instruction::MRET).unwrap_err(),
+        CPUError::PrivilegedInstruction(Instruction::MRET)
+    );
+    // unprivileged counters stay readable
+    cpu.execute(Instruction::CSRRS {
+        rd: 10,
+        rs1: 0,
+        csr: CSR_CYCLE,
+    })
+    .unwrap();
+
+    // with a kernel installed, the same access traps as an illegal instruction
+    let mut cpu = return_cpu();
+    cpu.set_trap_mode(TrapMode::Guest);
+    grant_user_memory(&mut cpu);
+    cpu.csrs.write(CSR_MTVEC, 0x40).unwrap();
+    cpu.set_privilege(Privilege::User);
+    load_program(&mut cpu, &[0x3000_2573]); // csrr a0, mstatus
+    let res = cpu.step_exec().unwrap();
+    assert_eq!(res.trap, Some(Exception::IllegalInstruction(0x3000_2573)));
+    assert_eq!(cpu.privilege(), Privilege::Machine);
+    assert_eq!(cpu.pc(), 0x40);
+}
+
+#[test]
+fn test_mstatus_trap_stack() {
+    let mut csrs = CsrFile::new();
+    csrs.write(CSR_MSTATUS, 0xFFFF_FFFF).unwrap();
+    assert_eq!(csrs.mstatus(), MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP);
+    // MPP = 01 names supervisor mode, which does not exist
+    csrs.write(CSR_MSTATUS, MSTATUS_MIE | (1 << 11)).unwrap();
+    assert_eq!(csrs.mstatus(), MSTATUS_MIE);
+
+    csrs.write(CSR_MTVEC, 0x80).unwrap();
+    assert_eq!(csrs.enter_trap(0x10, 2, 0, Privilege::User), 0x80);
+    assert_eq!(csrs.mstatus(), MSTATUS_MPIE);
+
+    assert_eq!(csrs.leave_trap(), (0x10, Privilege::User));
+    assert_eq!(csrs.mstatus(), MSTATUS_MIE | MSTATUS_MPIE);
+}
+
+#[test]
+fn test_timer_interrupt_preempts_loop() {
+    let mut cpu = return_cpu();
+    cpu.set_trap_mode(TrapMode::Guest);
+    load_program(
+        &mut cpu,
+        &[
+            0x0400_0293, // addi t0, x0, 0x40
+            0x3052_9073, // csrw mtvec, t0
+            0xF000_42B7, // lui t0, 0xF0004 (mtimecmp)
+            0x00A0_0313, // addi t1, x0, 10
+            0x0062_A023, // sw t1, 0(t0)
+            0x0002_A223, // sw x0, 4(t0)
+            0x0800_0313, // addi t1, x0, 0x80
+            0x3043_1073, // csrw mie, t1
+            0x3004_6073, // csrsi mstatus, 8
+            0x0000_006F, // j .
+        ],
+    );
+    cpu.memory.store_word(0x40, 0x3420_25F3).unwrap(); // csrr a1, mcause
+    cpu.memory.store_word(0x44, 0x02A0_0513).unwrap(); // addi a0, x0, 42
+    cpu.memory.store_word(0x48, 0x05D0_0893).unwrap(); // addi a7, x0, 93
+    cpu.memory.store_word(0x4C, 0x0000_0073).unwrap(); // ecall
+
+    assert_eq!(cpu.run(1000), StopReason::Halt(42));
+    assert_eq!(cpu.read_reg(11), Interrupt::MachineTimer.cause());
+    assert_eq!(cpu.csrs.mepc(), 0x24);
+    assert_eq!(cpu.csrs.mstatus() & MSTATUS_MIE, 0);
+    assert_ne!(cpu.csrs.mstatus() & MSTATUS_MPIE, 0);
+    assert_ne!(cpu.csrs.mip() & MIP_MTIP, 0);
+}
+
+#[test]
+fn test_software_interrupt_vectored() {
+    let mut cpu = return_cpu();
+    cpu.set_trap_mode(TrapMode::Guest);
+    load_program(&mut cpu, &[0x0000_0013, 0x0000_0013]);
+    cpu.csrs.write(CSR_MTVEC, 0x41).unwrap();
+    cpu.csrs.write(CSR_MIE, MIP_MSIP | MIP_MTIP).unwrap();
+
+    // pending but globally disabled in M-mode
+    cpu.memory.clint.set_msip(true);
+    cpu.memory.clint.set_mtimecmp(0);
+    let res = cpu.step_exec().unwrap();
+    assert_eq!(res.interrupt, None);
+    assert_eq!(cpu.pc(), 4);
+
+    // U-mode is always interruptible; software beats timer
+    cpu.set_privilege(Privilege::User);
+    let res = cpu.step_exec().unwrap();
+    assert_eq!(res.interrupt, Some(Interrupt::MachineSoftware));
+    assert_eq!(res.insn, None);
+    assert_eq!(cpu.pc(), 0x40 + 4 * 3);
+    assert_eq!(cpu.csrs.mepc(), 4);
+    assert_eq!(cpu.csrs.mcause(), (1 << 31) | 3);
+    assert_eq!(cpu.privilege(), Privilege::Machine);
+    assert_eq!(cpu.csrs.mstatus() & MSTATUS_MPP, 0);
+}
+
+#[test]
+fn test_wfi() {
+    let mut cpu = return_cpu();
+    load_program(
+        &mut cpu,
+        &[
+            0x1050_0073, // wfi
+            0xC010_2573, // rdtime a0
+            0x1050_0073, // wfi
+        ],
+    );
+    cpu.csrs.write(CSR_MIE, MIP_MTIP).unwrap();
+    cpu.memory.clint.set_mtimecmp(500);
+
+    // an armed timer is fast-forwarded to, even with mstatus.MIE clear
+    assert_eq!(cpu.step_exec().unwrap().stop, None);
+    cpu.step_exec().unwrap();
+    assert_eq!(cpu.read_reg(10), 501);
+
+    // nothing left that could wake the hart
+    cpu.csrs.write(CSR_MIE, 0).unwrap();
+    assert_eq!(cpu.run(10), StopReason::WaitForInterrupt);
+    assert_eq!(cpu.pc(), 12);
+
+    let mut cpu = return_cpu();
+    cpu.set_privilege(Privilege::User);
+    assert_eq!(
+        cpu.execute(Instruction::WFI).unwrap_err(),
+        CPUError::PrivilegedInstruction(Instruction::WFI)
+    );
+}
+
+#[test]
+fn test_time_reads_mtime() {
+    let mut cpu = return_cpu();
+    load_program(
+        &mut cpu,
+        &[
+            0xC010_2573, // rdtime a0
+            0xC010_2573, // rdtime a0
+        ],
+    );
+    cpu.step_exec().unwrap();
+    assert_eq!(cpu.read_reg(10), 0);
+    cpu.memory.clint.set_mtime(1_000);
+    cpu.step_exec().unwrap();
+    assert_eq!(cpu.read_reg(10), 1_000);
+    assert_eq!(cpu.memory.clint.mtime(), 1_001);
+}
+
+const USER_RX: u32 = PTE_V | PTE_R | PTE_X | PTE_U | PTE_A;
+const USER_RW: u32 = PTE_V | PTE_R | PTE_W | PTE_U | PTE_A | PTE_D;
+const USER_RO: u32 = PTE_V | PTE_R | PTE_U | PTE_A;
+
+/// Builds an Sv32 address space with its root table at 0x1000 and a single
+/// leaf table at 0x2000, mapping virtual 0x0000 (code), 0x1000 (data) and
+/// 0x2000 (a read-only alias of the data page) onto physical 0x3000/0x4000.
+fn sv32_cpu() -> CPU {
+    let mut cpu = CPU::new(0x8000, IsaConfig::default()).unwrap();
+    cpu.memory.store_word(0x1000, (2 << 10) | PTE_V).unwrap();
+    cpu.memory.store_word(0x2000, (3 << 10) | USER_RX).unwrap();
+    cpu.memory.store_word(0x2004, (4 << 10) | USER_RW).unwrap();
+    cpu.memory.store_word(0x2008, (4 << 10) | USER_RO).unwrap();
+    cpu.csrs.write(CSR_SATP, SATP_MODE_SV32 | 1).unwrap();
+    grant_user_memory(&mut cpu);
+    cpu.set_privilege(Privilege::User);
+    cpu
+}
+
+#[test]
+fn test_sv32_translation() {
+    let mut cpu = sv32_cpu();
+    cpu.store_word(0x1004, 0xCAFE).unwrap();
+    assert_eq!(cpu.memory.load_word(0x4004).unwrap(), 0xCAFE);
+    assert_eq!(cpu.load_word(0x2004).unwrap(), 0xCAFE);
+    assert_eq!(cpu.load_byte(0x2005).unwrap(), 0xCA);
+
+    // no write permission, no mapping, no execute permission
+    assert_eq!(cpu.store_word(0x2004, 1), Err(CPUError::PageFault(0x2004)));
+    assert_eq!(cpu.load_word(0x5000), Err(CPUError::PageFault(0x5000)));
+    assert_eq!(cpu.fetch(0x1000), Err(CPUError::PageFault(0x1000)));
+
+    cpu.memory.store_word(0x3000, 0x0050_0113).unwrap(); // addi sp, x0, 5
+    cpu.step_exec().unwrap();
+    assert_eq!(cpu.read_reg(2), 5);
+    assert_eq!(cpu.pc(), 4);
+
+    // M-mode ignores satp
+    cpu.set_privilege(Privilege::Machine);
+    assert_eq!(cpu.load_word(0x4004).unwrap(), 0xCAFE);
+    assert_eq!(cpu.load_word(0x1004).unwrap(), 0);
+}
+
+#[test]
+fn test_sv32_megapage() {
+    let mut cpu = sv32_cpu();
+    // virtual 4 MiB maps onto physical 0; a megapage not on a 4 MiB
+    // boundary is malformed
+    cpu.memory.store_word(0x1004, USER_RW).unwrap();
+    cpu.memory.store_word(0x1008, (1 << 10) | USER_RW).unwrap();
+    cpu.memory.store_word(0x4010, 0x1234).unwrap();
+    assert_eq!(cpu.load_word(0x0040_4010).unwrap(), 0x1234);
+    assert_eq!(
+        cpu.load_word(0x0080_0000),
+        Err(CPUError::PageFault(0x0080_0000))
+    );
+}
+
+#[test]
+fn test_sv32_page_fault_traps() {
+    let mut cpu = sv32_cpu();
+    cpu.set_trap_mode(TrapMode::Guest);
+    install_skip_handler(&mut cpu);
+    cpu.csrs.write(CSR_MTVEC, 0x40).unwrap();
+    let program = [
+        0x0000_23B7, // lui t2, 2
+        0x00A3_A023, // sw a0, 0(t2)
+        0x0003_A583, // lw a1, 0(t2)
+        0x0003_8067, // jalr x0, 0(t2)
+    ];
+    for (i, word) in program.iter().enumerate() {
+        cpu.memory.store_word(0x3000 + i as u32 * 4, *word).unwrap();
+    }
+    cpu.step_exec().unwrap();
+
+    let res = cpu.step_exec().unwrap();
+    assert_eq!(res.trap, Some(Exception::StorePageFault(0x2000)));
+    assert_eq!(cpu.csrs.read(CSR_MEPC).unwrap(), 4);
+    assert_eq!(cpu.privilege(), Privilege::Machine);
+    // the handler runs untranslated and returns to U-mode
+    for _ in 0..4 {
+        cpu.step_exec().unwrap();
+    }
+    assert_eq!(cpu.privilege(), Privilege::User);
+    assert_eq!(cpu.pc(), 8);
+
+    assert_eq!(cpu.step_exec().unwrap().trap, None);
+    cpu.step_exec().unwrap();
+    let res = cpu.step_exec().unwrap();
+    assert_eq!(res.trap, Some(Exception::InstructionPageFault(0x2000)));
+    assert_eq!(res.trap.unwrap().cause(), 12);
+    assert_eq!(cpu.csrs.read(CSR_MEPC).unwrap(), 0x2000);
+}
+
+#[test]
+fn test_sv32_tlb_flush() {
+    let mut cpu = sv32_cpu();
+    cpu.memory.store_word(0x3000, 0x1111).unwrap();
+    cpu.memory.store_word(0x4000, 0x2222).unwrap();
+    assert_eq!(cpu.load_word(0x1000).unwrap(), 0x2222);
+
+    // remapping the page is invisible until SFENCE.VMA
+    cpu.memory.store_word(0x2004, (3 << 10) | USER_RW).unwrap();
+    assert_eq!(cpu.load_word(0x1000).unwrap(), 0x2222);
+    let fence = Instruction::SFENCE_VMA { rs1: 5, rs2: 0 };
+    assert_eq!(
+        cpu.execute(fence.clone()).unwrap_err(),
+        CPUError::PrivilegedInstruction(fence.clone())
+    );
+    cpu.set_privilege(Privilege::Machine);
+    cpu.write_reg(5, 0x1000);
+    cpu.execute(fence).unwrap();
+    cpu.set_privilege(Privilege::User);
+    assert_eq!(cpu.load_word(0x1000).unwrap(), 0x1111);
+
+    // so is a new satp, which also drops every cached translation
+    cpu.memory.store_word(0x2004, (4 << 10) | USER_RW).unwrap();
+    cpu.set_privilege(Privilege::Machine);
+    cpu.write_reg(6, SATP_MODE_SV32 | 1);
+    cpu.execute(Instruction::CSRRW {
+        rd: 0,
+        rs1: 6,
+        csr: CSR_SATP,
+    })
+    .unwrap();
+    cpu.set_privilege(Privilege::User);
+    assert_eq!(cpu.load_word(0x1000).unwrap(), 0x2222);
+}
+
+#[test]
+fn test_sv32_megapage_flush() {
+    let mut cpu = sv32_cpu();
+    // virtual 4 MiB maps onto physical 0 as a megapage
+    cpu.memory.store_word(0x1004, USER_RW).unwrap();
+    cpu.memory.store_word(0x1010, 0x1111).unwrap();
+    cpu.memory.store_word(0x4010, 0x2222).unwrap();
+    assert_eq!(cpu.load_word(0x0040_1010).unwrap(), 0x1111);
+
+    // point it at the leaf table instead, which maps its second page onto
+    // 0x4000; fencing any address in the megapage drops every page of it
+    cpu.memory.store_word(0x1004, (2 << 10) | PTE_V).unwrap();
+    cpu.set_privilege(Privilege::Machine);
+    cpu.write_reg(5, 0x0040_0000);
+    cpu.execute(Instruction::SFENCE_VMA { rs1: 5, rs2: 0 })
+        .unwrap();
+    cpu.set_privilege(Privilege::User);
+    assert_eq!(cpu.load_word(0x0040_1010).unwrap(), 0x2222);
+}
+
+/// Grants U-mode full access to memory through a NAPOT entry 0 spanning the
+/// whole address space.
+fn grant_user_memory(cpu: &mut CPU) {
+    cpu.csrs.write(CSR_PMPADDR0, u32::MAX).unwrap();
+    cpu.csrs
+        .write(CSR_PMPCFG0, pmp_cfg(&[PMP_NAPOT | PMP_R | PMP_W | PMP_X]))
+        .unwrap();
+}
+
+fn pmp_cfg(entries: &[u8]) -> u32 {
+    entries
+        .iter()
+        .enumerate()
+        .fold(0, |cfg, (i, byte)| cfg | (*byte as u32) << (i * 8))
+}
+
+#[test]
+fn test_pmp_regions() {
+    let mut cpu = CPU::new(256, IsaConfig::default()).unwrap();
+    // with every entry off, U-mode has no memory at all
+    cpu.set_privilege(Privilege::User);
+    assert_eq!(cpu.load_word(0x10), Err(CPUError::PmpViolation(0x10)));
+    assert_eq!(cpu.fetch(0), Err(CPUError::PmpViolation(0)));
+    cpu.set_privilege(Privilege::Machine);
+    assert_eq!(cpu.load_word(0x10), Ok(0));
+
+    // 0x00..0x40 read/execute, 0x80..0x84 read/write, 0x84..0xC0 read-only
+    cpu.csrs.write(CSR_PMPADDR0, 0x7).unwrap();
+    cpu.csrs.write(CSR_PMPADDR0 + 1, 0x80 >> 2).unwrap();
+    cpu.csrs.write(CSR_PMPADDR0 + 2, 0xC0 >> 2).unwrap();
+    let cfg = pmp_cfg(&[
+        PMP_NAPOT | PMP_R | PMP_X,
+        PMP_NA4 | PMP_R | PMP_W,
+        PMP_TOR | PMP_R,
+    ]);
+    cpu.csrs.write(CSR_PMPCFG0, cfg).unwrap();
+    assert_eq!(cpu.csrs.read(CSR_PMPCFG0).unwrap(), cfg);
+
+    // PMP only binds U-mode until an entry is locked
+    cpu.store_word(0x10, 0x0050_0113).unwrap();
+    cpu.set_privilege(Privilege::User);
+    assert_eq!(cpu.fetch(0x10).unwrap(), 0x0050_0113);
+    assert_eq!(cpu.load_word(0x3C).unwrap(), 0);
+    assert_eq!(cpu.store_word(0x10, 0), Err(CPUError::PmpViolation(0x10)));
+    assert_eq!(cpu.load_word(0x40), Err(CPUError::PmpViolation(0x40)));
+
+    // the lower-numbered NA4 entry wins where it overlaps the TOR range
+    cpu.store_word(0x80, 1).unwrap();
+    assert_eq!(cpu.store_word(0x84, 1), Err(CPUError::PmpViolation(0x84)));
+    assert_eq!(cpu.load_word(0x84).unwrap(), 0);
+    assert_eq!(cpu.fetch(0x80), Err(CPUError::PmpViolation(0x80)));
+
+    // write without read is reserved
+    cpu.csrs
+        .write(CSR_PMPCFG0 + 1, pmp_cfg(&[PMP_NA4 | PMP_W]))
+        .unwrap();
+    assert_eq!(cpu.csrs.read(CSR_PMPCFG0 + 1).unwrap(), PMP_NA4 as u32);
+}
+
+#[test]
+fn test_pmp_lock() {
+    let mut cpu = CPU::new(256, IsaConfig::default()).unwrap();
+    cpu.csrs.write(CSR_PMPADDR0, 0x7).unwrap();
+    cpu.csrs.write(CSR_PMPADDR0 + 1, 0x40 >> 2).unwrap();
+    let cfg = pmp_cfg(&[PMP_NAPOT | PMP_R | PMP_L, PMP_TOR | PMP_R | PMP_L]);
+    cpu.csrs.write(CSR_PMPCFG0, cfg).unwrap();
+
+    // a locked entry binds M-mode and can no longer be changed, nor can the
+    // bottom bound of a locked TOR entry
+    assert_eq!(cpu.store_word(0x10, 1), Err(CPUError::PmpViolation(0x10)));
+    assert_eq!(cpu.store_word(0x40, 1), Ok(()));
+    cpu.csrs.write(CSR_PMPCFG0, 0).unwrap();
+    cpu.csrs.write(CSR_PMPADDR0, 0xFF).unwrap();
+    assert_eq!(cpu.csrs.read(CSR_PMPCFG0).unwrap(), cfg);
+    assert_eq!(cpu.csrs.read(CSR_PMPADDR0).unwrap(), 0x7);
+}
+
+#[test]
+fn test_pmp_access_fault_traps() {
+    let mut cpu = CPU::new(256, IsaConfig::default()).unwrap();
+    cpu.set_trap_mode(TrapMode::Guest);
+    install_skip_handler(&mut cpu);
+    cpu.csrs.write(CSR_MTVEC, 0x40).unwrap();
+    cpu.csrs.write(CSR_PMPADDR0, (0x80 >> 2) | 0x7).unwrap();
+    cpu.csrs
+        .write(CSR_PMPCFG0, pmp_cfg(&[PMP_NAPOT | PMP_R | PMP_X]))
+        .unwrap();
+    cpu.memory.store_word(0x80, 0x00A0_2023).unwrap(); // sw a0, 0(x0)
+    cpu.memory.store_word(0x84, 0x0C00_0067).unwrap(); // jr 0xC0(x0)
+    cpu.pc = 0x80;
+    cpu.set_privilege(Privilege::User);
+
+    let res = cpu.step_exec().unwrap();
+    assert_eq!(res.trap, Some(Exception::StoreAccessFault(0)));
+    assert_eq!(res.trap.unwrap().cause(), 7);
+    for _ in 0..4 {
+        cpu.step_exec().unwrap();
+    }
+    assert_eq!(cpu.privilege(), Privilege::User);
+    assert_eq!(cpu.pc(), 0x84);
+
+    cpu.step_exec().unwrap();
+    let res = cpu.step_exec().unwrap();
+    assert_eq!(res.trap, Some(Exception::InstructionAccessFault(0xC0)));
+    assert_eq!(cpu.csrs.read(CSR_MEPC).unwrap(), 0xC0);
 }
@@ -0,0 +1,164 @@
+use crate::{cpu::errors::CPUError, itxs::Instruction, memory::errors::MemoryError};
+
+/// Who handles faults, `ebreak` and interrupts: the host, or the guest's
+/// own trap handler at `mtvec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrapMode {
+    /// Faults end the run with an error, and interrupts are never taken.
+    #[default]
+    Host,
+    /// Faults and interrupts vector through `mtvec` like on hardware.
+    Guest,
+}
+
+/// An asynchronous interrupt raised by the CLINT.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    MachineSoftware,
+    MachineTimer,
+}
+
+impl Interrupt {
+    /// The interrupt's bit in `mip`/`mie`, and its vector in vectored mode.
+    pub fn code(&self) -> u32 {
+        match self {
+            Interrupt::MachineSoftware => 3,
+            Interrupt::MachineTimer => 7,
+        }
+    }
+
+    /// The value written to `mcause`, with the interrupt bit set.
+    pub fn cause(&self) -> u32 {
+        (1 << 31) | self.code()
+    }
+}
+
+/// A synchronous exception, carrying the value the hart records in `mtval`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exception {
+    InstructionMisaligned(u32),
+    InstructionAccessFault(u32),
+    IllegalInstruction(u32),
+    Breakpoint(u32),
+    LoadMisaligned(u32),
+    LoadAccessFault(u32),
+    StoreMisaligned(u32),
+    StoreAccessFault(u32),
+    EcallFromU,
+    EcallFromM,
+    InstructionPageFault(u32),
+    LoadPageFault(u32),
+    StorePageFault(u32),
+}
+
+impl Exception {
+    /// The exception code written to `mcause`.
+    pub fn cause(&self) -> u32 {
+        match self {
+            Exception::InstructionMisaligned(_) => 0,
+            Exception::InstructionAccessFault(_) => 1,
+            Exception::IllegalInstruction(_) => 2,
+            Exception::Breakpoint(_) => 3,
+            Exception::LoadMisaligned(_) => 4,
+            Exception::LoadAccessFault(_) => 5,
+            Exception::StoreMisaligned(_) => 6,
+            Exception::StoreAccessFault(_) => 7,
+            Exception::EcallFromU => 8,
+            Exception::EcallFromM => 11,
+            Exception::InstructionPageFault(_) => 12,
+            Exception::LoadPageFault(_) => 13,
+            Exception::StorePageFault(_) => 15,
+        }
+    }
+
+    /// The value written to `mtval`.
+    pub fn tval(&self) -> u32 {
+        match *self {
+            Exception::InstructionMisaligned(v)
+            | Exception::InstructionAccessFault(v)
+            | Exception::IllegalInstruction(v)
+            | Exception::Breakpoint(v)
+            | Exception::LoadMisaligned(v)
+            | Exception::LoadAccessFault(v)
+            | Exception::StoreMisaligned(v)
+            | Exception::StoreAccessFault(v)
+            | Exception::InstructionPageFault(v)
+            | Exception::LoadPageFault(v)
+            | Exception::StorePageFault(v) => v,
+            Exception::EcallFromU | Exception::EcallFromM => 0,
+        }
+    }
+
+    /// The exception for a failed instruction fetch.
+    pub(crate) fn from_fetch(err: &CPUError) -> Self {
+        match *err {
+            CPUError::Memory(MemoryError::UnalignedAccess { addr, .. }) => {
+                Exception::InstructionMisaligned(addr)
+            }
+            CPUError::PageFault(addr) => Exception::InstructionPageFault(addr),
+            CPUError::PmpViolation(addr) => Exception::InstructionAccessFault(addr),
+            CPUError::Memory(MemoryError::OutOfBounds { addr, .. }) => {
+                Exception::InstructionAccessFault(addr)
+            }
+            _ => Exception::InstructionAccessFault(0),
+        }
+    }
+
+    /// The exception `insn` (encoded as `word`) raises by failing with `err`,
+    /// or `None` for host-side failures the guest cannot handle, such as a
+    /// syscall handler erroring out.
+    pub(crate) fn from_error(err: &CPUError, insn: &Instruction, word: u32) -> Option<Self> {
+        let store = is_store(insn);
+        let exc = match *err {
+            CPUError::UnknownSyscall(_) => Exception::EcallFromM,
+            CPUError::UserEcall => Exception::EcallFromU,
+            _ if *insn == Instruction::ECALL => return None,
+            CPUError::Memory(MemoryError::UnalignedAccess { addr, .. }) if store => {
+                Exception::StoreMisaligned(addr)
+            }
+            CPUError::Memory(MemoryError::UnalignedAccess { addr, .. }) => {
+                Exception::LoadMisaligned(addr)
+            }
+            CPUError::Memory(MemoryError::OutOfBounds { addr, .. }) if store => {
+                Exception::StoreAccessFault(addr)
+            }
+            CPUError::Memory(MemoryError::OutOfBounds { addr, .. }) => {
+                Exception::LoadAccessFault(addr)
+            }
+            CPUError::PageFault(addr) if store => Exception::StorePageFault(addr),
+            CPUError::PageFault(addr) => Exception::LoadPageFault(addr),
+            CPUError::PmpViolation(addr) if store => Exception::StoreAccessFault(addr),
+            CPUError::PmpViolation(addr) => Exception::LoadAccessFault(addr),
+            CPUError::MisalignedTarget(target) => Exception::InstructionMisaligned(target),
+            CPUError::Decode(_)
+            | CPUError::InvalidRegister(_)
+            | CPUError::InvalidCsr(_)
+            | CPUError::UnsupportedInstruction(_)
+            | CPUError::PrivilegedInstruction(_) => Exception::IllegalInstruction(word),
+            CPUError::AddressOutOfRange(_) => return None,
+        };
+        Some(exc)
+    }
+}
+
+/// Whether a memory fault in `insn` is a store/AMO fault rather than a load
+/// fault.
+fn is_store(insn: &Instruction) -> bool {
+    use Instruction::*;
+    matches!(
+        insn,
+        SB { .. }
+            | SH { .. }
+            | SW { .. }
+            | SC_W { .. }
+            | AMOSWAP_W { .. }
+            | AMOADD_W { .. }
+            | AMOXOR_W { .. }
+            | AMOAND_W { .. }
+            | AMOOR_W { .. }
+            | AMOMIN_W { .. }
+            | AMOMAX_W { .. }
+            | AMOMINU_W { .. }
+            | AMOMAXU_W { .. }
+    )
+}
@@ -0,0 +1,292 @@
+use crate::{
+    constants::REG_A7,
+    cpu::{
+        StopReason,
+        alu::{alu, branch_taken},
+        errors::CPUError,
+    },
+    cpu64::{CPU64, guest_addr},
+    itxs::Instruction,
+    syscall::SyscallContext,
+};
+
+/// Sign-extends the low word of a W-suffixed op's result to 64 bits.
+fn sext_w(v: u32) -> u64 {
+    v as i32 as i64 as u64
+}
+
+impl CPU64 {
+    /// Executes `instr` as if it were fetched from the current `pc`, leaving
+    /// `pc` pointing at the next instruction to run.
+    pub fn execute(&mut self, instr: Instruction) -> Result<Option<StopReason>, CPUError> {
+        use Instruction::*;
+        let pc = self.pc;
+        let mut next_pc = pc.wrapping_add(4);
+        let mut stop = None;
+        match instr {
+            LUI { rd, imm } => {
+                self.write_reg(rd as usize, sext_w(imm));
+            }
+            AUIPC { rd, imm } => {
+                self.write_reg(rd as usize, pc.wrapping_add(sext_w(imm)));
+            }
+            JAL { rd, imm } => {
+                let target = self.jump_target(pc.wrapping_add(imm as i64 as u64))?;
+                self.write_reg(rd as usize, next_pc);
+                next_pc = target;
+            }
+            JALR { rd, rs1, imm } => {
+                let base = self.read_reg(rs1 as usize);
+                let target = self.jump_target(base.wrapping_add(imm as i64 as u64) & !1)?;
+                self.write_reg(rd as usize, next_pc);
+                next_pc = target;
+            }
+
+            LB { rd, rs1, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.memory.load_byte(addr)? as i8 as i64 as u64;
+                self.write_reg(rd as usize, val);
+            }
+            LH { rd, rs1, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.memory.load_half(addr)? as i16 as i64 as u64;
+                self.write_reg(rd as usize, val);
+            }
+            LW { rd, rs1, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = sext_w(self.memory.load_word(addr)?);
+                self.write_reg(rd as usize, val);
+            }
+            LD { rd, rs1, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.memory.load_double(addr)?;
+                self.write_reg(rd as usize, val);
+            }
+            LBU { rd, rs1, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.memory.load_byte(addr)? as u64;
+                self.write_reg(rd as usize, val);
+            }
+            LHU { rd, rs1, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.memory.load_half(addr)? as u64;
+                self.write_reg(rd as usize, val);
+            }
+            LWU { rd, rs1, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.memory.load_word(addr)? as u64;
+                self.write_reg(rd as usize, val);
+            }
+            SB { rs1, rs2, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.read_reg(rs2 as usize);
+                self.memory.store_byte(addr, val as u8)?;
+            }
+            SH { rs1, rs2, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.read_reg(rs2 as usize);
+                self.memory.store_half(addr, val as u16)?;
+            }
+            SW { rs1, rs2, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.read_reg(rs2 as usize);
+                self.memory.store_word(addr, val as u32)?;
+            }
+            SD { rs1, rs2, imm } => {
+                let addr = self.addr(rs1, imm)?;
+                let val = self.read_reg(rs2 as usize);
+                self.memory.store_double(addr, val)?;
+            }
+
+            // the I and M ops RV32 shares, see `cpu::alu`
+            ADDI { .. }
+            | SLTI { .. }
+            | SLTIU { .. }
+            | XORI { .. }
+            | ORI { .. }
+            | ANDI { .. }
+            | SLLI { .. }
+            | SRLI { .. }
+            | SRAI { .. }
+            | ADD { .. }
+            | SUB { .. }
+            | SLL { .. }
+            | SLT { .. }
+            | SLTU { .. }
+            | XOR { .. }
+            | SRL { .. }
+            | SRA { .. }
+            | OR { .. }
+            | AND { .. }
+            | MUL { .. }
+            | MULH { .. }
+            | MULHSU { .. }
+            | MULHU { .. }
+            | DIV { .. }
+            | DIVU { .. }
+            | REM { .. }
+            | REMU { .. } => {
+                let (rd, res) = alu(&instr, |r| self.read_reg(r as usize)).expect("an integer op");
+                self.write_reg(rd as usize, res);
+            }
+            BEQ { imm, .. }
+            | BNE { imm, .. }
+            | BLT { imm, .. }
+            | BGE { imm, .. }
+            | BLTU { imm, .. }
+            | BGEU { imm, .. } => {
+                if branch_taken(&instr, |r| self.read_reg(r as usize)) == Some(true) {
+                    next_pc = self.jump_target(pc.wrapping_add(imm as i64 as u64))?;
+                }
+            }
+
+            ADDIW { rd, rs1, imm } => {
+                let v = self.read_reg(rs1 as usize) as u32;
+                self.write_reg(rd as usize, sext_w(v.wrapping_add(imm as u32)));
+            }
+            SLLIW { rd, rs1, shamt } => {
+                let v = self.read_reg(rs1 as usize) as u32;
+                self.write_reg(rd as usize, sext_w(v << shamt));
+            }
+            SRLIW { rd, rs1, shamt } => {
+                let v = self.read_reg(rs1 as usize) as u32;
+                self.write_reg(rd as usize, sext_w(v >> shamt));
+            }
+            SRAIW { rd, rs1, shamt } => {
+                let v = self.read_reg(rs1 as usize) as i32;
+                self.write_reg(rd as usize, sext_w((v >> shamt) as u32));
+            }
+            ADDW { rd, rs1, rs2 } => self.op_w(rd, rs1, rs2, |a, b| a.wrapping_add(b)),
+            SUBW { rd, rs1, rs2 } => self.op_w(rd, rs1, rs2, |a, b| a.wrapping_sub(b)),
+            SLLW { rd, rs1, rs2 } => self.op_w(rd, rs1, rs2, |a, b| a << (b & 0x1F)),
+            SRLW { rd, rs1, rs2 } => self.op_w(rd, rs1, rs2, |a, b| a >> (b & 0x1F)),
+            SRAW { rd, rs1, rs2 } => {
+                self.op_w(rd, rs1, rs2, |a, b| ((a as i32) >> (b & 0x1F)) as u32)
+            }
+            MULW { rd, rs1, rs2 } => self.op_w(rd, rs1, rs2, |a, b| a.wrapping_mul(b)),
+            DIVW { rd, rs1, rs2 } => self.op_w(rd, rs1, rs2, |a, b| {
+                if b == 0 {
+                    u32::MAX
+                } else {
+                    (a as i32).wrapping_div(b as i32) as u32
+                }
+            }),
+            DIVUW { rd, rs1, rs2 } => self.op_w(rd, rs1, rs2, |a, b| {
+                if b == 0 { u32::MAX } else { a.wrapping_div(b) }
+            }),
+            REMW { rd, rs1, rs2 } => self.op_w(rd, rs1, rs2, |a, b| {
+                if b == 0 {
+                    a
+                } else {
+                    (a as i32).wrapping_rem(b as i32) as u32
+                }
+            }),
+            REMUW { rd, rs1, rs2 } => {
+                self.op_w(
+                    rd,
+                    rs1,
+                    rs2,
+                    |a, b| if b == 0 { a } else { a.wrapping_rem(b) },
+                )
+            }
+
+            FENCE { .. } | FENCE_I => {}
+            ECALL => stop = self.ecall()?,
+            EBREAK => stop = Some(StopReason::Breakpoint),
+
+            // no CSRs, privilege levels, A or bit-manipulation on this hart
+            MRET
+            | WFI
+            | SFENCE_VMA { .. }
+            | CSRRW { .. }
+            | CSRRS { .. }
+            | CSRRC { .. }
+            | CSRRWI { .. }
+            | CSRRSI { .. }
+            | CSRRCI { .. }
+            | LR_W { .. }
+            | SC_W { .. }
+            | AMOSWAP_W { .. }
+            | AMOADD_W { .. }
+            | AMOXOR_W { .. }
+            | AMOAND_W { .. }
+            | AMOOR_W { .. }
+            | AMOMIN_W { .. }
+            | AMOMAX_W { .. }
+            | AMOMINU_W { .. }
+            | AMOMAXU_W { .. }
+            | SH1ADD { .. }
+            | SH2ADD { .. }
+            | SH3ADD { .. }
+            | ANDN { .. }
+            | ORN { .. }
+            | XNOR { .. }
+            | MIN { .. }
+            | MINU { .. }
+            | MAX { .. }
+            | MAXU { .. }
+            | ROL { .. }
+            | ROR { .. }
+            | BCLR { .. }
+            | BEXT { .. }
+            | BINV { .. }
+            | BSET { .. }
+            | CLZ { .. }
+            | CTZ { .. }
+            | CPOP { .. }
+            | SEXT_B { .. }
+            | SEXT_H { .. }
+            | ZEXT_H { .. }
+            | ORC_B { .. }
+            | REV8 { .. }
+            | RORI { .. }
+            | BCLRI { .. }
+            | BEXTI { .. }
+            | BINVI { .. }
+            | BSETI { .. } => return Err(CPUError::UnsupportedInstruction(instr)),
+        }
+        self.pc = next_pc;
+        Ok(stop)
+    }
+
+    /// Effective address `rs1 + imm`, narrowed to the guest address space.
+    fn addr(&self, rs1: u8, imm: i32) -> Result<u32, CPUError> {
+        guest_addr(self.read_reg(rs1 as usize).wrapping_add(imm as i64 as u64))
+    }
+
+    /// Checks that a jump or taken branch lands inside the guest address
+    /// space on a four-byte boundary; there is no C extension here.
+    fn jump_target(&self, target: u64) -> Result<u64, CPUError> {
+        let addr = guest_addr(target)?;
+        if !addr.is_multiple_of(4) {
+            return Err(CPUError::MisalignedTarget(addr));
+        }
+        Ok(target)
+    }
+
+    /// Applies `f` to the low words of both operands, sign-extending the
+    /// 32-bit result.
+    fn op_w(&mut self, rd: u8, rs1: u8, rs2: u8, f: impl FnOnce(u32, u32) -> u32) {
+        let a = self.read_reg(rs1 as usize) as u32;
+        let b = self.read_reg(rs2 as usize) as u32;
+        self.write_reg(rd as usize, sext_w(f(a, b)));
+    }
+
+    /// Syscall handlers work on a 32-bit view of the register file. Any
+    /// register a handler changes is written back sign-extended.
+    fn ecall(&mut self) -> Result<Option<StopReason>, CPUError> {
+        let num = self.read_reg(REG_A7) as u32;
+        let mut regs = self.regs.map(|r| r as u32);
+        let mut ctx = SyscallContext::new(&mut regs, &mut self.memory);
+        let stop = self.syscalls.dispatch(num, &mut ctx)?;
+        for (reg, low) in self.regs.iter_mut().zip(regs) {
+            if *reg as u32 != low {
+                *reg = sext_w(low);
+            }
+        }
+        if let Some(StopReason::Halt(code)) = stop {
+            self.exit_code = Some(code);
+        }
+        Ok(stop)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    cpu::{StepResult, StopReason, errors::CPUError, run_steps},
+    decoder::decode_rv64_with,
+    isa::IsaConfig,
+    memory::{Memory, errors::MemoryError},
+    syscall::SyscallRegistry,
+};
+
+mod exec;
+#[cfg(test)]
+mod tests;
+
+/// An RV64IM hart. It shares the decoder, memory, syscall registry and
+/// integer semantics with the 32-bit [`CPU`](crate::cpu::CPU), but keeps
+/// 64-bit registers and `pc`. Guest addresses must still fall inside the
+/// 32-bit memory space. There are no CSRs, so faults are never trapped and
+/// always end the run.
+#[derive(Default)]
+pub struct CPU64 {
+    regs: [u64; 32],
+    pc: u64,
+    exit_code: Option<i32>,
+    pub memory: Memory,
+    pub syscalls: SyscallRegistry,
+}
+
+impl CPU64 {
+    pub fn new(mem_size: usize) -> Result<Self, MemoryError> {
+        Ok(CPU64 {
+            regs: [0; 32],
+            pc: 0,
+            exit_code: None,
+            memory: Memory::new(mem_size)?,
+            syscalls: SyscallRegistry::default(),
+        })
+    }
+
+    /// The extensions this hart implements on top of the RV64I base: just
+    /// M. `IsaConfig` only tracks extensions, not the register width.
+    pub fn isa() -> IsaConfig {
+        IsaConfig::rv32im()
+    }
+
+    pub fn read_reg(&self, idx: usize) -> u64 {
+        if idx == 0 { 0 } else { self.regs[idx] }
+    }
+
+    pub fn write_reg(&mut self, idx: usize, value: u64) {
+        if idx != 0 && idx < 32 {
+            self.regs[idx] = value;
+        }
+    }
+
+    pub fn pc(&self) -> u64 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u64) {
+        self.pc = pc;
+    }
+
+    /// The code passed to the `exit` syscall, once the guest has exited.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Fetches, decodes and executes the instruction at `pc`, leaving `pc`
+    /// on the faulting instruction if any stage fails.
+    pub fn step_exec(&mut self) -> Result<StepResult, CPUError> {
+        let word = self.memory.fetch(guest_addr(self.pc)?)?;
+        let insn = decode_rv64_with(word, Self::isa())?;
+        let stop = self.execute(insn.clone())?;
+        Ok(StepResult {
+            insn: Some(insn),
+            stop,
+            trap: None,
+            interrupt: None,
+        })
+    }
+
+    /// Runs until the guest exits, hits a breakpoint, faults, or `max_steps`
+    /// instructions have executed.
+    pub fn run(&mut self, max_steps: u64) -> StopReason {
+        run_steps(self.exit_code, max_steps, || self.step_exec())
+    }
+}
+
+/// Narrows a 64-bit effective address to the 32-bit guest address space.
+fn guest_addr(addr: u64) -> Result<u32, CPUError> {
+    u32::try_from(addr).map_err(|_| CPUError::AddressOutOfRange(addr))
+}
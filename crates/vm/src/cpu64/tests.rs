@@ -0,0 +1,222 @@
+use super::*;
+use crate::decoder::errors::DecodeError;
+use crate::itxs::Instruction;
+
+fn load_program(cpu: &mut CPU64, words: &[u32]) {
+    for (i, word) in words.iter().enumerate() {
+        cpu.memory.store_word(i as u32 * 4, *word).unwrap();
+    }
+}
+
+#[test]
+fn test_run_rv64_program() {
+    let mut cpu = CPU64::new(256).unwrap();
+    load_program(
+        &mut cpu,
+        &[
+            0xFFF0_0513, // addi a0, x0, -1
+            0x0205_5593, // srli a1, a0, 32
+            0x00B5_863B, // addw a2, a1, a1
+            0x04B0_3023, // sd a1, 64(x0)
+            0x0400_3683, // ld a3, 64(x0)
+            0x0400_6703, // lwu a4, 64(x0)
+            0x0400_2783, // lw a5, 64(x0)
+            0x0285_9293, // slli t0, a1, 40
+            0x43F2_D313, // srai t1, t0, 63
+            0x40B0_03BB, // subw t2, x0, a1
+            0x0015_841B, // addiw s0, a1, 1
+            0x41F5_549B, // sraiw s1, a0, 31
+            0x0000_0513, // addi a0, x0, 0
+            0x05D0_0893, // addi a7, x0, 93
+            0x0000_0073, // ecall
+        ],
+    );
+    assert_eq!(cpu.run(100), StopReason::Halt(0));
+    assert_eq!(cpu.read_reg(11), 0xFFFF_FFFF);
+    assert_eq!(cpu.read_reg(12), 0xFFFF_FFFF_FFFF_FFFE);
+    assert_eq!(cpu.read_reg(13), 0xFFFF_FFFF);
+    assert_eq!(cpu.read_reg(14), 0xFFFF_FFFF);
+    assert_eq!(cpu.read_reg(15), u64::MAX);
+    assert_eq!(cpu.read_reg(5), 0xFFFF_FF00_0000_0000);
+    assert_eq!(cpu.read_reg(6), u64::MAX);
+    assert_eq!(cpu.read_reg(7), 1);
+    assert_eq!(cpu.read_reg(8), 0);
+    assert_eq!(cpu.read_reg(9), u64::MAX);
+    assert_eq!(cpu.pc(), 15 * 4);
+}
+
+#[test]
+fn test_word_ops_sign_extend() {
+    use Instruction::*;
+    let (rd, rs1, rs2) = (3, 1, 2);
+    let a = 0x1234_5678_8000_0000;
+    let b = 0xFFFF_FFFF_0000_0002;
+    let cases = [
+        (ADDW { rd, rs1, rs2 }, 0xFFFF_FFFF_8000_0002),
+        (SUBW { rd, rs1, rs2 }, 0x0000_0000_7FFF_FFFE),
+        (SLLW { rd, rs1, rs2 }, 0),
+        (SRLW { rd, rs1, rs2 }, 0x0000_0000_2000_0000),
+        (SRAW { rd, rs1, rs2 }, 0xFFFF_FFFF_E000_0000),
+        (MULW { rd, rs1, rs2 }, 0),
+        (DIVW { rd, rs1, rs2 }, 0xFFFF_FFFF_C000_0000),
+        (DIVUW { rd, rs1, rs2 }, 0x0000_0000_4000_0000),
+        (REMW { rd, rs1, rs2 }, 0),
+        (REMUW { rd, rs1, rs2 }, 0),
+        (ADD { rd, rs1, rs2 }, 0x1234_5677_8000_0002),
+        (SLL { rd, rs1, rs2 }, 0x48D1_59E2_0000_0000),
+        (MULHU { rd, rs1, rs2 }, 0x1234_5678_6DCB_A987),
+    ];
+    for (instr, expected) in cases {
+        let mut cpu = CPU64::default();
+        cpu.write_reg(1, a);
+        cpu.write_reg(2, b);
+        cpu.execute(instr.clone()).unwrap();
+        assert_eq!(cpu.read_reg(3), expected, "{instr:?}");
+    }
+}
+
+#[test]
+fn test_word_division_by_zero() {
+    let mut cpu = CPU64::default();
+    cpu.write_reg(1, 0xFFFF_FFFF_8000_0000);
+    cpu.execute(Instruction::DIVW {
+        rd: 3,
+        rs1: 1,
+        rs2: 0,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), u64::MAX);
+    cpu.execute(Instruction::REMUW {
+        rd: 3,
+        rs1: 1,
+        rs2: 0,
+    })
+    .unwrap();
+    assert_eq!(cpu.read_reg(3), 0xFFFF_FFFF_8000_0000);
+}
+
+#[test]
+fn test_full_width_multiply_and_divide() {
+    let mut cpu = CPU64::default();
+    cpu.write_reg(1, i64::MIN as u64);
+    cpu.write_reg(2, u64::MAX);
+    for (instr, expected) in [
+        (
+            Instruction::MULH {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+            },
+            0,
+        ),
+        (
+            Instruction::MULHU {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+            },
+            0x7FFF_FFFF_FFFF_FFFF,
+        ),
+        (
+            Instruction::MULHSU {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+            },
+            0x8000_0000_0000_0000,
+        ),
+        (
+            Instruction::DIV {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+            },
+            i64::MIN as u64,
+        ),
+        (
+            Instruction::REM {
+                rd: 3,
+                rs1: 1,
+                rs2: 0,
+            },
+            i64::MIN as u64,
+        ),
+        (
+            Instruction::SRA {
+                rd: 3,
+                rs1: 1,
+                rs2: 2,
+            },
+            u64::MAX,
+        ),
+    ] {
+        cpu.execute(instr.clone()).unwrap();
+        assert_eq!(cpu.read_reg(3), expected, "{instr:?}");
+    }
+}
+
+#[test]
+fn test_misaligned_jump_target() {
+    let mut cpu = CPU64::default();
+    let err = cpu.execute(Instruction::JAL { rd: 1, imm: 6 }).unwrap_err();
+    assert_eq!(err, CPUError::MisalignedTarget(6));
+    assert_eq!(cpu.read_reg(1), 0);
+
+    cpu.write_reg(2, 2);
+    let err = cpu
+        .execute(Instruction::BEQ {
+            rs1: 2,
+            rs2: 2,
+            imm: 6,
+        })
+        .unwrap_err();
+    assert_eq!(err, CPUError::MisalignedTarget(6));
+    assert_eq!(cpu.pc(), 0);
+}
+
+#[test]
+fn test_address_outside_guest_memory() {
+    let mut cpu = CPU64::default();
+    cpu.write_reg(1, 1 << 32);
+    let err = cpu
+        .execute(Instruction::LD {
+            rd: 2,
+            rs1: 1,
+            imm: 8,
+        })
+        .unwrap_err();
+    assert_eq!(err, CPUError::AddressOutOfRange((1 << 32) + 8));
+    assert_eq!(cpu.pc(), 0);
+}
+
+#[test]
+fn test_unsupported_instruction() {
+    let mut cpu = CPU64::default();
+    for instr in [
+        Instruction::CLZ { rd: 1, rs1: 2 },
+        Instruction::CSRRW {
+            rd: 1,
+            rs1: 2,
+            csr: 0x300,
+        },
+        Instruction::MRET,
+    ] {
+        assert_eq!(
+            cpu.execute(instr.clone()).unwrap_err(),
+            CPUError::UnsupportedInstruction(instr)
+        );
+    }
+}
+
+#[test]
+fn test_decodes_only_its_own_extensions() {
+    let mut cpu = CPU64::default();
+    load_program(&mut cpu, &[0xC000_2573]); // rdcycle a0
+    assert_eq!(
+        cpu.step_exec(),
+        Err(CPUError::Decode(DecodeError::ExtensionDisabled(
+            0xC000_2573
+        )))
+    );
+    assert_eq!(cpu.pc(), 0);
+}
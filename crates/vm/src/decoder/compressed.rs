@@ -0,0 +1,325 @@
+use crate::{isa::IsaConfig, itxs::Instruction};
+
+use super::errors::DecodeError;
+
+const REG_RA: u8 = 1;
+const REG_SP: u8 = 2;
+
+/// Like [`decode_compressed`], but rejects every 16-bit encoding unless `isa`
+/// enables the C extension.
+pub fn decode_compressed_with(half: u16, isa: IsaConfig) -> Result<Instruction, DecodeError> {
+    let instr = decode_compressed(half)?;
+    if !isa.c {
+        return Err(DecodeError::ExtensionDisabled(half as u32));
+    }
+    Ok(instr)
+}
+
+/// Expands a 16-bit RVC instruction into the base instruction it stands for.
+pub fn decode_compressed(half: u16) -> Result<Instruction, DecodeError> {
+    let h = half as u32;
+    let invalid = Err(DecodeError::InvalidInstruction(h));
+    match (h & 0x3, c_funct3(h)) {
+        // C.ADDI4SPN
+        (0x0, 0x0) => {
+            let imm = ciw_imm(h);
+            if imm == 0 {
+                return invalid;
+            }
+            Ok(Instruction::ADDI {
+                rd: creg_lo(h),
+                rs1: REG_SP,
+                imm,
+            })
+        }
+        // C.LW
+        (0x0, 0x2) => Ok(Instruction::LW {
+            rd: creg_lo(h),
+            rs1: creg_hi(h),
+            imm: cl_imm(h),
+        }),
+        // C.SW
+        (0x0, 0x6) => Ok(Instruction::SW {
+            rs1: creg_hi(h),
+            rs2: creg_lo(h),
+            imm: cl_imm(h),
+        }),
+        // C.NOP / C.ADDI
+        (0x1, 0x0) => Ok(Instruction::ADDI {
+            rd: c_rd(h),
+            rs1: c_rd(h),
+            imm: ci_imm(h),
+        }),
+        // C.JAL
+        (0x1, 0x1) => Ok(Instruction::JAL {
+            rd: REG_RA,
+            imm: cj_imm(h),
+        }),
+        // C.LI
+        (0x1, 0x2) => Ok(Instruction::ADDI {
+            rd: c_rd(h),
+            rs1: 0,
+            imm: ci_imm(h),
+        }),
+        // C.ADDI16SP
+        (0x1, 0x3) if c_rd(h) == REG_SP => {
+            let imm = addi16sp_imm(h);
+            if imm == 0 {
+                return invalid;
+            }
+            Ok(Instruction::ADDI {
+                rd: REG_SP,
+                rs1: REG_SP,
+                imm,
+            })
+        }
+        // C.LUI
+        (0x1, 0x3) => {
+            let imm = ci_imm(h);
+            if imm == 0 {
+                return invalid;
+            }
+            Ok(Instruction::LUI {
+                rd: c_rd(h),
+                imm: (imm as u32) << 12,
+            })
+        }
+        (0x1, 0x4) => decode_compressed_alu(h),
+        // C.J
+        (0x1, 0x5) => Ok(Instruction::JAL {
+            rd: 0,
+            imm: cj_imm(h),
+        }),
+        // C.BEQZ
+        (0x1, 0x6) => Ok(Instruction::BEQ {
+            rs1: creg_hi(h),
+            rs2: 0,
+            imm: cb_imm(h),
+        }),
+        // C.BNEZ
+        (0x1, 0x7) => Ok(Instruction::BNE {
+            rs1: creg_hi(h),
+            rs2: 0,
+            imm: cb_imm(h),
+        }),
+        // C.SLLI
+        (0x2, 0x0) => {
+            if (h >> 12) & 0x1 != 0 {
+                return invalid;
+            }
+            Ok(Instruction::SLLI {
+                rd: c_rd(h),
+                rs1: c_rd(h),
+                shamt: c_rs2(h),
+            })
+        }
+        // C.LWSP
+        (0x2, 0x2) => {
+            if c_rd(h) == 0 {
+                return invalid;
+            }
+            Ok(Instruction::LW {
+                rd: c_rd(h),
+                rs1: REG_SP,
+                imm: lwsp_imm(h),
+            })
+        }
+        (0x2, 0x4) => {
+            let (rd, rs2) = (c_rd(h), c_rs2(h));
+            match ((h >> 12) & 0x1, rd, rs2) {
+                // C.JR
+                (0, 0, 0) => invalid,
+                (0, rs1, 0) => Ok(Instruction::JALR { rd: 0, rs1, imm: 0 }),
+                // C.MV
+                (0, rd, rs2) => Ok(Instruction::ADD { rd, rs1: 0, rs2 }),
+                (_, 0, 0) => Ok(Instruction::EBREAK),
+                // C.JALR
+                (_, rs1, 0) => Ok(Instruction::JALR {
+                    rd: REG_RA,
+                    rs1,
+                    imm: 0,
+                }),
+                // C.ADD
+                (_, rd, rs2) => Ok(Instruction::ADD { rd, rs1: rd, rs2 }),
+            }
+        }
+        // C.SWSP
+        (0x2, 0x6) => Ok(Instruction::SW {
+            rs1: REG_SP,
+            rs2: c_rs2(h),
+            imm: swsp_imm(h),
+        }),
+        _ => invalid,
+    }
+}
+
+/// Expands a 16-bit RVC instruction as RV64C reads it: the RV32 C.JAL and
+/// C.FLW/C.FSW slots hold C.ADDIW and the doubleword loads and stores,
+/// C.SUBW/C.ADDW are added, and shift amounts gain a sixth bit.
+pub fn decode_compressed_rv64(half: u16) -> Result<Instruction, DecodeError> {
+    let h = half as u32;
+    let shamt = (bit(h, 12, 5) | ((h >> 2) & 0x1F)) as u8;
+    match (h & 0x3, c_funct3(h)) {
+        // C.LD
+        (0x0, 0x3) => Ok(Instruction::LD {
+            rd: creg_lo(h),
+            rs1: creg_hi(h),
+            imm: cld_imm(h),
+        }),
+        // C.SD
+        (0x0, 0x7) => Ok(Instruction::SD {
+            rs1: creg_hi(h),
+            rs2: creg_lo(h),
+            imm: cld_imm(h),
+        }),
+        // C.ADDIW
+        (0x1, 0x1) => {
+            if c_rd(h) == 0 {
+                return Err(DecodeError::InvalidInstruction(h));
+            }
+            Ok(Instruction::ADDIW {
+                rd: c_rd(h),
+                rs1: c_rd(h),
+                imm: ci_imm(h),
+            })
+        }
+        (0x1, 0x4) => {
+            let rd = creg_hi(h);
+            let rs2 = creg_lo(h);
+            match ((h >> 10) & 0x3, (h >> 12) & 0x1, (h >> 5) & 0x3) {
+                // C.SRLI / C.SRAI
+                (0x0, _, _) => Ok(Instruction::SRLI { rd, rs1: rd, shamt }),
+                (0x1, _, _) => Ok(Instruction::SRAI { rd, rs1: rd, shamt }),
+                // C.SUBW / C.ADDW
+                (0x3, 1, 0x0) => Ok(Instruction::SUBW { rd, rs1: rd, rs2 }),
+                (0x3, 1, 0x1) => Ok(Instruction::ADDW { rd, rs1: rd, rs2 }),
+                _ => decode_compressed_alu(h),
+            }
+        }
+        // C.SLLI
+        (0x2, 0x0) => Ok(Instruction::SLLI {
+            rd: c_rd(h),
+            rs1: c_rd(h),
+            shamt,
+        }),
+        // C.LDSP
+        (0x2, 0x3) => {
+            if c_rd(h) == 0 {
+                return Err(DecodeError::InvalidInstruction(h));
+            }
+            Ok(Instruction::LD {
+                rd: c_rd(h),
+                rs1: REG_SP,
+                imm: ldsp_imm(h),
+            })
+        }
+        // C.SDSP
+        (0x2, 0x7) => Ok(Instruction::SD {
+            rs1: REG_SP,
+            rs2: c_rs2(h),
+            imm: sdsp_imm(h),
+        }),
+        _ => decode_compressed(half),
+    }
+}
+
+fn decode_compressed_alu(h: u32) -> Result<Instruction, DecodeError> {
+    let rd = creg_hi(h);
+    let rs2 = creg_lo(h);
+    let bit12 = (h >> 12) & 0x1;
+    match ((h >> 10) & 0x3, bit12, (h >> 5) & 0x3) {
+        // C.SRLI / C.SRAI; shamt[5] is reserved on RV32
+        (0x0 | 0x1, 1, _) => Err(DecodeError::InvalidInstruction(h)),
+        (0x0, _, _) => Ok(Instruction::SRLI {
+            rd,
+            rs1: rd,
+            shamt: c_rs2(h),
+        }),
+        (0x1, _, _) => Ok(Instruction::SRAI {
+            rd,
+            rs1: rd,
+            shamt: c_rs2(h),
+        }),
+        (0x2, _, _) => Ok(Instruction::ANDI {
+            rd,
+            rs1: rd,
+            imm: ci_imm(h),
+        }),
+        (0x3, 0, 0x0) => Ok(Instruction::SUB { rd, rs1: rd, rs2 }),
+        (0x3, 0, 0x1) => Ok(Instruction::XOR { rd, rs1: rd, rs2 }),
+        (0x3, 0, 0x2) => Ok(Instruction::OR { rd, rs1: rd, rs2 }),
+        (0x3, 0, 0x3) => Ok(Instruction::AND { rd, rs1: rd, rs2 }),
+        _ => Err(DecodeError::InvalidInstruction(h)),
+    }
+}
+
+fn c_funct3(h: u32) -> u8 {
+    ((h >> 13) & 0x7) as u8
+}
+fn c_rd(h: u32) -> u8 {
+    ((h >> 7) & 0x1F) as u8
+}
+fn c_rs2(h: u32) -> u8 {
+    ((h >> 2) & 0x1F) as u8
+}
+/// rs1'/rd' in bits 9:7, mapped onto x8..x15.
+fn creg_hi(h: u32) -> u8 {
+    (((h >> 7) & 0x7) + 8) as u8
+}
+/// rs2'/rd' in bits 4:2, mapped onto x8..x15.
+fn creg_lo(h: u32) -> u8 {
+    (((h >> 2) & 0x7) + 8) as u8
+}
+
+fn bit(h: u32, from: u32, to: u32) -> u32 {
+    ((h >> from) & 0x1) << to
+}
+
+fn ci_imm(h: u32) -> i32 {
+    let raw = bit(h, 12, 5) | ((h >> 2) & 0x1F);
+    ((raw as i32) << 26) >> 26
+}
+fn ciw_imm(h: u32) -> i32 {
+    (bit(h, 5, 3) | bit(h, 6, 2) | (((h >> 7) & 0xF) << 6) | (((h >> 11) & 0x3) << 4)) as i32
+}
+fn cl_imm(h: u32) -> i32 {
+    (bit(h, 5, 6) | bit(h, 6, 2) | (((h >> 10) & 0x7) << 3)) as i32
+}
+fn cld_imm(h: u32) -> i32 {
+    ((((h >> 10) & 0x7) << 3) | (((h >> 5) & 0x3) << 6)) as i32
+}
+fn lwsp_imm(h: u32) -> i32 {
+    (bit(h, 12, 5) | (((h >> 4) & 0x7) << 2) | (((h >> 2) & 0x3) << 6)) as i32
+}
+fn swsp_imm(h: u32) -> i32 {
+    ((((h >> 9) & 0xF) << 2) | (((h >> 7) & 0x3) << 6)) as i32
+}
+fn ldsp_imm(h: u32) -> i32 {
+    (bit(h, 12, 5) | (((h >> 5) & 0x3) << 3) | (((h >> 2) & 0x7) << 6)) as i32
+}
+fn sdsp_imm(h: u32) -> i32 {
+    ((((h >> 10) & 0x7) << 3) | (((h >> 7) & 0x7) << 6)) as i32
+}
+fn addi16sp_imm(h: u32) -> i32 {
+    let raw = bit(h, 12, 9) | bit(h, 6, 4) | bit(h, 5, 6) | (((h >> 3) & 0x3) << 7) | bit(h, 2, 5);
+    ((raw as i32) << 22) >> 22
+}
+fn cj_imm(h: u32) -> i32 {
+    let raw = bit(h, 12, 11)
+        | bit(h, 11, 4)
+        | (((h >> 9) & 0x3) << 8)
+        | bit(h, 8, 10)
+        | bit(h, 7, 6)
+        | bit(h, 6, 7)
+        | (((h >> 3) & 0x7) << 1)
+        | bit(h, 2, 5);
+    ((raw as i32) << 20) >> 20
+}
+fn cb_imm(h: u32) -> i32 {
+    let raw = bit(h, 12, 8)
+        | (((h >> 10) & 0x3) << 3)
+        | (((h >> 5) & 0x3) << 6)
+        | (((h >> 3) & 0x3) << 1)
+        | bit(h, 2, 5);
+    ((raw as i32) << 23) >> 23
+}
@@ -2,4 +2,6 @@
 pub enum DecodeError {
     UnknownOpcode(u8),
     InvalidInstruction(u32),
+    /// A valid encoding from an extension the `IsaConfig` leaves out.
+    ExtensionDisabled(u32),
 }
@@ -1,10 +1,29 @@
+mod compressed;
 pub mod errors;
+mod rv64;
+#[cfg(test)]
 mod tests;
 
-use crate::itxs::Instruction;
+use crate::{isa::IsaConfig, itxs::Instruction};
 use errors::DecodeError;
 
+pub use compressed::{decode_compressed, decode_compressed_rv64, decode_compressed_with};
+pub use rv64::{decode_rv64, decode_rv64_with};
+
+/// Whether the instruction parcel starting with `word`'s low half is a 16-bit
+/// RVC encoding rather than a 32-bit one.
+pub fn is_compressed(word: u32) -> bool {
+    word & 0x3 != 0x3
+}
+
+/// Decodes `word` with every extension the VM implements enabled.
 pub fn decode(word: u32) -> Result<Instruction, DecodeError> {
+    decode_with(word, IsaConfig::default())
+}
+
+/// Decodes `word`, rejecting instructions from extensions `isa` leaves out
+/// with [`DecodeError::ExtensionDisabled`].
+pub fn decode_with(word: u32, isa: IsaConfig) -> Result<Instruction, DecodeError> {
     let opcode = (word & 0x7F) as u8;
     match opcode {
         0x37 => decode_u_type(word, |rd, imm| Instruction::LUI { rd, imm }),
@@ -12,23 +31,27 @@ pub fn decode(word: u32) -> Result<Instruction, DecodeError> {
         0x6F => decode_j_type(word, |rd, imm| Instruction::JAL { rd, imm }),
         0x67 => decode_i_type(word, |rd, rs1, imm| Instruction::JALR { rd, rs1, imm }),
         0x03 => decode_load_type(word),
-        0x13 => {
-            let f3 = funct3(word);
-            if f3 == 0x1 || f3 == 0x5 {
-                decode_shift_imm_type(word)
-            } else {
-                decode_op_imm_type(word)
-            }
-        }
+        0x13 => decode_op_imm_type(word, isa),
         0x23 => decode_store_type(word),
         0x63 => decode_branch_type(word),
-        0x33 => decode_op_type(word),
-        0x73 => decode_system(word),
-        0x3B => decode_op_type(word),
+        0x33 => decode_op_type(word, isa),
+        0x73 => decode_system(word, isa),
+        0x0F => decode_fence_type(word),
+        0x2F => decode_amo_type(word).and_then(|instr| gate(isa.a, word, instr)),
         _ => Err(DecodeError::UnknownOpcode(opcode)),
     }
 }
 
+/// Passes `instr` through if its extension is `enabled`, and reports `word`
+/// as [`DecodeError::ExtensionDisabled`] otherwise.
+fn gate(enabled: bool, word: u32, instr: Instruction) -> Result<Instruction, DecodeError> {
+    if enabled {
+        Ok(instr)
+    } else {
+        Err(DecodeError::ExtensionDisabled(word))
+    }
+}
+
 fn rd(word: u32) -> u8 {
     ((word >> 7) & 0x1F) as u8
 }
@@ -93,75 +116,248 @@ where
     Ok(ctor(rd(word), immediate_j(word)))
 }
 fn decode_load_type(word: u32) -> Result<Instruction, DecodeError> {
-    Ok(Instruction::LOAD {
-        rd: rd(word),
-        rs1: rs1(word),
-        imm: immediate_i(word),
-    })
-}
-fn decode_op_imm_type(word: u32) -> Result<Instruction, DecodeError> {
-    Ok(Instruction::OP_IMM {
-        rd: rd(word),
-        rs1: rs1(word),
-        imm: immediate_i(word),
-    })
-}
-fn decode_shift_imm_type(word: u32) -> Result<Instruction, DecodeError> {
-    let shamt = ((word >> 20) & 0x1F) as u8;
-    let is_srai = funct7(word) == 0x20;
-    Ok(Instruction::SHIFT_IMM {
-        rd: rd(word),
-        rs1: rs1(word),
-        shamt,
-        is_srai,
-    })
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    let imm = immediate_i(word);
+    match funct3(word) {
+        0x0 => Ok(Instruction::LB { rd, rs1, imm }),
+        0x1 => Ok(Instruction::LH { rd, rs1, imm }),
+        0x2 => Ok(Instruction::LW { rd, rs1, imm }),
+        0x4 => Ok(Instruction::LBU { rd, rs1, imm }),
+        0x5 => Ok(Instruction::LHU { rd, rs1, imm }),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
+}
+fn decode_op_imm_type(word: u32, isa: IsaConfig) -> Result<Instruction, DecodeError> {
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    let imm = immediate_i(word);
+    match funct3(word) {
+        0x0 => Ok(Instruction::ADDI { rd, rs1, imm }),
+        0x2 => Ok(Instruction::SLTI { rd, rs1, imm }),
+        0x3 => Ok(Instruction::SLTIU { rd, rs1, imm }),
+        0x4 => Ok(Instruction::XORI { rd, rs1, imm }),
+        0x6 => Ok(Instruction::ORI { rd, rs1, imm }),
+        0x7 => Ok(Instruction::ANDI { rd, rs1, imm }),
+        0x1 | 0x5 => decode_shift_imm_type(word, isa),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
+}
+fn decode_shift_imm_type(word: u32, isa: IsaConfig) -> Result<Instruction, DecodeError> {
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    let shamt = rs2(word);
+    match (funct3(word), funct7(word), shamt) {
+        (0x1, 0x00, _) => Ok(Instruction::SLLI { rd, rs1, shamt }),
+        (0x5, 0x00, _) => Ok(Instruction::SRLI { rd, rs1, shamt }),
+        (0x5, 0x20, _) => Ok(Instruction::SRAI { rd, rs1, shamt }),
+
+        (0x1, 0x30, 0x00) => gate(isa.zbb, word, Instruction::CLZ { rd, rs1 }),
+        (0x1, 0x30, 0x01) => gate(isa.zbb, word, Instruction::CTZ { rd, rs1 }),
+        (0x1, 0x30, 0x02) => gate(isa.zbb, word, Instruction::CPOP { rd, rs1 }),
+        (0x1, 0x30, 0x04) => gate(isa.zbb, word, Instruction::SEXT_B { rd, rs1 }),
+        (0x1, 0x30, 0x05) => gate(isa.zbb, word, Instruction::SEXT_H { rd, rs1 }),
+        (0x5, 0x30, _) => gate(isa.zbb, word, Instruction::RORI { rd, rs1, shamt }),
+        (0x5, 0x14, 0x07) => gate(isa.zbb, word, Instruction::ORC_B { rd, rs1 }),
+        (0x5, 0x34, 0x18) => gate(isa.zbb, word, Instruction::REV8 { rd, rs1 }),
+
+        (0x1, 0x24, _) => gate(isa.zbs, word, Instruction::BCLRI { rd, rs1, shamt }),
+        (0x5, 0x24, _) => gate(isa.zbs, word, Instruction::BEXTI { rd, rs1, shamt }),
+        (0x1, 0x34, _) => gate(isa.zbs, word, Instruction::BINVI { rd, rs1, shamt }),
+        (0x1, 0x14, _) => gate(isa.zbs, word, Instruction::BSETI { rd, rs1, shamt }),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
 }
 fn decode_store_type(word: u32) -> Result<Instruction, DecodeError> {
-    Ok(Instruction::STORE {
-        rs1: rs1(word),
-        rs2: rs2(word),
-        imm: immediate_s(word),
-    })
+    let rs1 = rs1(word);
+    let rs2 = rs2(word);
+    let imm = immediate_s(word);
+    match funct3(word) {
+        0x0 => Ok(Instruction::SB { rs1, rs2, imm }),
+        0x1 => Ok(Instruction::SH { rs1, rs2, imm }),
+        0x2 => Ok(Instruction::SW { rs1, rs2, imm }),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
 }
 fn decode_branch_type(word: u32) -> Result<Instruction, DecodeError> {
-    Ok(Instruction::BRANCH {
-        rs1: rs1(word),
-        rs2: rs2(word),
-        imm: immediate_b(word),
-    })
+    let rs1 = rs1(word);
+    let rs2 = rs2(word);
+    let imm = immediate_b(word);
+    match funct3(word) {
+        0x0 => Ok(Instruction::BEQ { rs1, rs2, imm }),
+        0x1 => Ok(Instruction::BNE { rs1, rs2, imm }),
+        0x4 => Ok(Instruction::BLT { rs1, rs2, imm }),
+        0x5 => Ok(Instruction::BGE { rs1, rs2, imm }),
+        0x6 => Ok(Instruction::BLTU { rs1, rs2, imm }),
+        0x7 => Ok(Instruction::BGEU { rs1, rs2, imm }),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
 }
-fn decode_op_type(word: u32) -> Result<Instruction, DecodeError> {
+fn decode_op_type(word: u32, isa: IsaConfig) -> Result<Instruction, DecodeError> {
     let f3 = funct3(word);
     let f7 = funct7(word);
     let rd = rd(word);
     let rs1 = rs1(word);
     let rs2 = rs2(word);
     match (f3, f7) {
-        (0x0, 0x00) => Ok(Instruction::OP {
+        (0x0, 0x00) => Ok(Instruction::ADD { rd, rs1, rs2 }),
+        (0x0, 0x20) => Ok(Instruction::SUB { rd, rs1, rs2 }),
+        (0x1, 0x00) => Ok(Instruction::SLL { rd, rs1, rs2 }),
+        (0x2, 0x00) => Ok(Instruction::SLT { rd, rs1, rs2 }),
+        (0x3, 0x00) => Ok(Instruction::SLTU { rd, rs1, rs2 }),
+        (0x4, 0x00) => Ok(Instruction::XOR { rd, rs1, rs2 }),
+        (0x5, 0x00) => Ok(Instruction::SRL { rd, rs1, rs2 }),
+        (0x5, 0x20) => Ok(Instruction::SRA { rd, rs1, rs2 }),
+        (0x6, 0x00) => Ok(Instruction::OR { rd, rs1, rs2 }),
+        (0x7, 0x00) => Ok(Instruction::AND { rd, rs1, rs2 }),
+
+        (0x0, 0x01) => gate(isa.m, word, Instruction::MUL { rd, rs1, rs2 }),
+        (0x1, 0x01) => gate(isa.m, word, Instruction::MULH { rd, rs1, rs2 }),
+        (0x2, 0x01) => gate(isa.m, word, Instruction::MULHSU { rd, rs1, rs2 }),
+        (0x3, 0x01) => gate(isa.m, word, Instruction::MULHU { rd, rs1, rs2 }),
+        (0x4, 0x01) => gate(isa.m, word, Instruction::DIV { rd, rs1, rs2 }),
+        (0x5, 0x01) => gate(isa.m, word, Instruction::DIVU { rd, rs1, rs2 }),
+        (0x6, 0x01) => gate(isa.m, word, Instruction::REM { rd, rs1, rs2 }),
+        (0x7, 0x01) => gate(isa.m, word, Instruction::REMU { rd, rs1, rs2 }),
+
+        (0x2, 0x10) => gate(isa.zba, word, Instruction::SH1ADD { rd, rs1, rs2 }),
+        (0x4, 0x10) => gate(isa.zba, word, Instruction::SH2ADD { rd, rs1, rs2 }),
+        (0x6, 0x10) => gate(isa.zba, word, Instruction::SH3ADD { rd, rs1, rs2 }),
+
+        (0x7, 0x20) => gate(isa.zbb, word, Instruction::ANDN { rd, rs1, rs2 }),
+        (0x6, 0x20) => gate(isa.zbb, word, Instruction::ORN { rd, rs1, rs2 }),
+        (0x4, 0x20) => gate(isa.zbb, word, Instruction::XNOR { rd, rs1, rs2 }),
+        (0x4, 0x05) => gate(isa.zbb, word, Instruction::MIN { rd, rs1, rs2 }),
+        (0x5, 0x05) => gate(isa.zbb, word, Instruction::MINU { rd, rs1, rs2 }),
+        (0x6, 0x05) => gate(isa.zbb, word, Instruction::MAX { rd, rs1, rs2 }),
+        (0x7, 0x05) => gate(isa.zbb, word, Instruction::MAXU { rd, rs1, rs2 }),
+        (0x1, 0x30) => gate(isa.zbb, word, Instruction::ROL { rd, rs1, rs2 }),
+        (0x5, 0x30) => gate(isa.zbb, word, Instruction::ROR { rd, rs1, rs2 }),
+        (0x4, 0x04) if rs2 == 0 => gate(isa.zbb, word, Instruction::ZEXT_H { rd, rs1 }),
+
+        (0x1, 0x24) => gate(isa.zbs, word, Instruction::BCLR { rd, rs1, rs2 }),
+        (0x5, 0x24) => gate(isa.zbs, word, Instruction::BEXT { rd, rs1, rs2 }),
+        (0x1, 0x34) => gate(isa.zbs, word, Instruction::BINV { rd, rs1, rs2 }),
+        (0x1, 0x14) => gate(isa.zbs, word, Instruction::BSET { rd, rs1, rs2 }),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
+}
+/// The rd and rs1 fields of FENCE and FENCE.I, and the immediate of FENCE.I,
+/// are reserved for future use and ignored, as the spec requires.
+fn decode_fence_type(word: u32) -> Result<Instruction, DecodeError> {
+    match funct3(word) {
+        0x0 => Ok(Instruction::FENCE {
+            fm: (word >> 28) as u8,
+            pred: ((word >> 24) & 0xF) as u8,
+            succ: ((word >> 20) & 0xF) as u8,
+        }),
+        0x1 => Ok(Instruction::FENCE_I),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
+}
+fn decode_amo_type(word: u32) -> Result<Instruction, DecodeError> {
+    if funct3(word) != 0x2 {
+        return Err(DecodeError::InvalidInstruction(word));
+    }
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    let rs2 = rs2(word);
+    let aq = (word >> 26) & 0x1 != 0;
+    let rl = (word >> 25) & 0x1 != 0;
+    match word >> 27 {
+        0x02 if rs2 == 0 => Ok(Instruction::LR_W { rd, rs1, aq, rl }),
+        0x03 => Ok(Instruction::SC_W {
             rd,
             rs1,
             rs2,
-            funct7: f7,
+            aq,
+            rl,
         }),
-        (0x0, 0x20) => Ok(Instruction::OP {
+        0x01 => Ok(Instruction::AMOSWAP_W {
             rd,
             rs1,
             rs2,
-            funct7: f7,
+            aq,
+            rl,
+        }),
+        0x00 => Ok(Instruction::AMOADD_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        }),
+        0x04 => Ok(Instruction::AMOXOR_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        }),
+        0x0C => Ok(Instruction::AMOAND_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        }),
+        0x08 => Ok(Instruction::AMOOR_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        }),
+        0x10 => Ok(Instruction::AMOMIN_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        }),
+        0x14 => Ok(Instruction::AMOMAX_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        }),
+        0x18 => Ok(Instruction::AMOMINU_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        }),
+        0x1C => Ok(Instruction::AMOMAXU_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
         }),
-
-        (0x0, 0x01) => Ok(Instruction::MUL { rd, rs1, rs2 }),
-        (0x4, 0x01) => Ok(Instruction::DIV { rd, rs1, rs2 }),
-        (0x5, 0x01) => Ok(Instruction::DIVU { rd, rs1, rs2 }),
-        (0x6, 0x01) => Ok(Instruction::REM { rd, rs1, rs2 }),
-        (0x7, 0x01) => Ok(Instruction::REMU { rd, rs1, rs2 }),
         _ => Err(DecodeError::InvalidInstruction(word)),
     }
 }
-fn decode_system(word: u32) -> Result<Instruction, DecodeError> {
+fn decode_system(word: u32, isa: IsaConfig) -> Result<Instruction, DecodeError> {
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    let csr = (word >> 20) as u16;
     match (funct3(word), immediate_i(word)) {
+        (0x0, _) if word >> 25 == 0x09 && rd == 0 => Ok(Instruction::SFENCE_VMA {
+            rs1,
+            rs2: rs2(word),
+        }),
         (0x0, 0x0) => Ok(Instruction::ECALL),
         (0x0, 0x1) => Ok(Instruction::EBREAK),
+        (0x0, 0x302) if rd == 0 && rs1 == 0 => Ok(Instruction::MRET),
+        (0x0, 0x105) if rd == 0 && rs1 == 0 => Ok(Instruction::WFI),
+        (0x1, _) => gate(isa.zicsr, word, Instruction::CSRRW { rd, rs1, csr }),
+        (0x2, _) => gate(isa.zicsr, word, Instruction::CSRRS { rd, rs1, csr }),
+        (0x3, _) => gate(isa.zicsr, word, Instruction::CSRRC { rd, rs1, csr }),
+        (0x5, _) => gate(isa.zicsr, word, Instruction::CSRRWI { rd, uimm: rs1, csr }),
+        (0x6, _) => gate(isa.zicsr, word, Instruction::CSRRSI { rd, uimm: rs1, csr }),
+        (0x7, _) => gate(isa.zicsr, word, Instruction::CSRRCI { rd, uimm: rs1, csr }),
         _ => Err(DecodeError::InvalidInstruction(word)),
     }
 }
@@ -0,0 +1,94 @@
+use crate::{isa::IsaConfig, itxs::Instruction};
+
+use super::{
+    decode_load_type, decode_op_imm_type, decode_store_type, decode_with, errors::DecodeError,
+    funct3, funct7, gate, immediate_i, immediate_s, rd, rs1, rs2,
+};
+
+/// Decodes `word` as an RV64 instruction with every extension the VM
+/// implements enabled. Shift amounts are six bits wide and the 64-bit
+/// loads/stores and W-suffixed word ops are recognised; the rest of the
+/// encoding space is shared with RV32.
+pub fn decode_rv64(word: u32) -> Result<Instruction, DecodeError> {
+    decode_rv64_with(word, IsaConfig::default())
+}
+
+/// Like [`decode_rv64`], rejecting instructions from extensions `isa` leaves
+/// out with [`DecodeError::ExtensionDisabled`].
+pub fn decode_rv64_with(word: u32, isa: IsaConfig) -> Result<Instruction, DecodeError> {
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    match ((word & 0x7F) as u8, funct3(word)) {
+        (0x03, 0x3) => Ok(Instruction::LD {
+            rd,
+            rs1,
+            imm: immediate_i(word),
+        }),
+        (0x03, 0x6) => Ok(Instruction::LWU {
+            rd,
+            rs1,
+            imm: immediate_i(word),
+        }),
+        (0x03, _) => decode_load_type(word),
+        (0x23, 0x3) => Ok(Instruction::SD {
+            rs1,
+            rs2: rs2(word),
+            imm: immediate_s(word),
+        }),
+        (0x23, _) => decode_store_type(word),
+        (0x13, 0x1 | 0x5) => decode_shift_imm64_type(word),
+        (0x13, _) => decode_op_imm_type(word, isa),
+        (0x1B, _) => decode_op_imm32_type(word),
+        (0x3B, _) => decode_op32_type(word, isa),
+        _ => decode_with(word, isa),
+    }
+}
+
+fn decode_shift_imm64_type(word: u32) -> Result<Instruction, DecodeError> {
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    let shamt = ((word >> 20) & 0x3F) as u8;
+    match (funct3(word), word >> 26) {
+        (0x1, 0x00) => Ok(Instruction::SLLI { rd, rs1, shamt }),
+        (0x5, 0x00) => Ok(Instruction::SRLI { rd, rs1, shamt }),
+        (0x5, 0x10) => Ok(Instruction::SRAI { rd, rs1, shamt }),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
+}
+
+fn decode_op_imm32_type(word: u32) -> Result<Instruction, DecodeError> {
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    let shamt = rs2(word);
+    match (funct3(word), funct7(word)) {
+        (0x0, _) => Ok(Instruction::ADDIW {
+            rd,
+            rs1,
+            imm: immediate_i(word),
+        }),
+        (0x1, 0x00) => Ok(Instruction::SLLIW { rd, rs1, shamt }),
+        (0x5, 0x00) => Ok(Instruction::SRLIW { rd, rs1, shamt }),
+        (0x5, 0x20) => Ok(Instruction::SRAIW { rd, rs1, shamt }),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
+}
+
+fn decode_op32_type(word: u32, isa: IsaConfig) -> Result<Instruction, DecodeError> {
+    let rd = rd(word);
+    let rs1 = rs1(word);
+    let rs2 = rs2(word);
+    match (funct3(word), funct7(word)) {
+        (0x0, 0x00) => Ok(Instruction::ADDW { rd, rs1, rs2 }),
+        (0x0, 0x20) => Ok(Instruction::SUBW { rd, rs1, rs2 }),
+        (0x1, 0x00) => Ok(Instruction::SLLW { rd, rs1, rs2 }),
+        (0x5, 0x00) => Ok(Instruction::SRLW { rd, rs1, rs2 }),
+        (0x5, 0x20) => Ok(Instruction::SRAW { rd, rs1, rs2 }),
+
+        (0x0, 0x01) => gate(isa.m, word, Instruction::MULW { rd, rs1, rs2 }),
+        (0x4, 0x01) => gate(isa.m, word, Instruction::DIVW { rd, rs1, rs2 }),
+        (0x5, 0x01) => gate(isa.m, word, Instruction::DIVUW { rd, rs1, rs2 }),
+        (0x6, 0x01) => gate(isa.m, word, Instruction::REMW { rd, rs1, rs2 }),
+        (0x7, 0x01) => gate(isa.m, word, Instruction::REMUW { rd, rs1, rs2 }),
+        _ => Err(DecodeError::InvalidInstruction(word)),
+    }
+}
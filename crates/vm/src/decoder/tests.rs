@@ -29,11 +29,10 @@ fn decode_add() {
     let code = 0x007302B3;
     assert_eq!(
         decode(code).unwrap(),
-        Instruction::OP {
+        Instruction::ADD {
             rd: 5,
             rs1: 6,
-            rs2: 7,
-            funct7: 0x00
+            rs2: 7
         }
     );
 }
@@ -43,15 +42,87 @@ fn decode_sub() {
     let code = 0x407302B3;
     assert_eq!(
         decode(code).unwrap(),
-        Instruction::OP {
+        Instruction::SUB {
             rd: 5,
             rs1: 6,
-            rs2: 7,
-            funct7: 0x20
+            rs2: 7
         }
     );
 }
 
+#[test]
+fn decode_register_alu() {
+    assert_eq!(
+        decode(0x007312B3).unwrap(),
+        Instruction::SLL {
+            rd: 5,
+            rs1: 6,
+            rs2: 7
+        }
+    );
+    assert_eq!(
+        decode(0x007322B3).unwrap(),
+        Instruction::SLT {
+            rd: 5,
+            rs1: 6,
+            rs2: 7
+        }
+    );
+    assert_eq!(
+        decode(0x007332B3).unwrap(),
+        Instruction::SLTU {
+            rd: 5,
+            rs1: 6,
+            rs2: 7
+        }
+    );
+    assert_eq!(
+        decode(0x007342B3).unwrap(),
+        Instruction::XOR {
+            rd: 5,
+            rs1: 6,
+            rs2: 7
+        }
+    );
+    assert_eq!(
+        decode(0x007352B3).unwrap(),
+        Instruction::SRL {
+            rd: 5,
+            rs1: 6,
+            rs2: 7
+        }
+    );
+    assert_eq!(
+        decode(0x407352B3).unwrap(),
+        Instruction::SRA {
+            rd: 5,
+            rs1: 6,
+            rs2: 7
+        }
+    );
+    assert_eq!(
+        decode(0x007362B3).unwrap(),
+        Instruction::OR {
+            rd: 5,
+            rs1: 6,
+            rs2: 7
+        }
+    );
+    assert_eq!(
+        decode(0x007372B3).unwrap(),
+        Instruction::AND {
+            rd: 5,
+            rs1: 6,
+            rs2: 7
+        }
+    );
+    let bad_funct7 = 0x807312B3;
+    assert_eq!(
+        decode(bad_funct7),
+        Err(DecodeError::InvalidInstruction(bad_funct7))
+    );
+}
+
 #[test]
 fn decode_mul_div_rem() {
     assert_eq!(
@@ -62,6 +133,30 @@ fn decode_mul_div_rem() {
             rs2: 3
         }
     );
+    assert_eq!(
+        decode(0x023110B3).unwrap(),
+        Instruction::MULH {
+            rd: 1,
+            rs1: 2,
+            rs2: 3
+        }
+    );
+    assert_eq!(
+        decode(0x023120B3).unwrap(),
+        Instruction::MULHSU {
+            rd: 1,
+            rs1: 2,
+            rs2: 3
+        }
+    );
+    assert_eq!(
+        decode(0x023130B3).unwrap(),
+        Instruction::MULHU {
+            rd: 1,
+            rs1: 2,
+            rs2: 3
+        }
+    );
     assert_eq!(
         decode(0x023140B3).unwrap(),
         Instruction::DIV {
@@ -102,12 +197,61 @@ fn decode_addi() {
     let code = 0xFFB10093;
     assert_eq!(
         decode(code).unwrap(),
-        Instruction::OP_IMM {
+        Instruction::ADDI {
+            rd: 1,
+            rs1: 2,
+            imm: -5
+        }
+    );
+}
+
+#[test]
+fn decode_op_imm_funct3() {
+    // SLTI x1, x2, -5
+    assert_eq!(
+        decode(0xFFB12093).unwrap(),
+        Instruction::SLTI {
             rd: 1,
             rs1: 2,
             imm: -5
         }
     );
+    // SLTIU x1, x2, -5
+    assert_eq!(
+        decode(0xFFB13093).unwrap(),
+        Instruction::SLTIU {
+            rd: 1,
+            rs1: 2,
+            imm: -5
+        }
+    );
+    // XORI x1, x2, -1
+    assert_eq!(
+        decode(0xFFF14093).unwrap(),
+        Instruction::XORI {
+            rd: 1,
+            rs1: 2,
+            imm: -1
+        }
+    );
+    // ORI x1, x2, 0xF0
+    assert_eq!(
+        decode(0x0F016093).unwrap(),
+        Instruction::ORI {
+            rd: 1,
+            rs1: 2,
+            imm: 0xF0
+        }
+    );
+    // ANDI x1, x2, 0xFF
+    assert_eq!(
+        decode(0x0FF17093).unwrap(),
+        Instruction::ANDI {
+            rd: 1,
+            rs1: 2,
+            imm: 0xFF
+        }
+    );
 }
 
 #[test]
@@ -115,7 +259,7 @@ fn decode_load_store() {
     let lw = 0x00412083;
     assert_eq!(
         decode(lw).unwrap(),
-        Instruction::LOAD {
+        Instruction::LW {
             rd: 1,
             rs1: 2,
             imm: 4
@@ -124,7 +268,7 @@ fn decode_load_store() {
     let sw = 0x0030A423;
     assert_eq!(
         decode(sw).unwrap(),
-        Instruction::STORE {
+        Instruction::SW {
             rs1: 1,
             rs2: 3,
             imm: 8
@@ -132,18 +276,129 @@ fn decode_load_store() {
     );
 }
 
+#[test]
+fn decode_sub_word_load_store() {
+    assert_eq!(
+        decode(0x00410083).unwrap(),
+        Instruction::LB {
+            rd: 1,
+            rs1: 2,
+            imm: 4
+        }
+    );
+    assert_eq!(
+        decode(0x00411083).unwrap(),
+        Instruction::LH {
+            rd: 1,
+            rs1: 2,
+            imm: 4
+        }
+    );
+    assert_eq!(
+        decode(0x00414083).unwrap(),
+        Instruction::LBU {
+            rd: 1,
+            rs1: 2,
+            imm: 4
+        }
+    );
+    assert_eq!(
+        decode(0x00415083).unwrap(),
+        Instruction::LHU {
+            rd: 1,
+            rs1: 2,
+            imm: 4
+        }
+    );
+    assert_eq!(
+        decode(0x00308423).unwrap(),
+        Instruction::SB {
+            rs1: 1,
+            rs2: 3,
+            imm: 8
+        }
+    );
+    assert_eq!(
+        decode(0x00309423).unwrap(),
+        Instruction::SH {
+            rs1: 1,
+            rs2: 3,
+            imm: 8
+        }
+    );
+    let bad_load = 0x00413083;
+    assert_eq!(
+        decode(bad_load),
+        Err(DecodeError::InvalidInstruction(bad_load))
+    );
+    let bad_store = 0x0030C423;
+    assert_eq!(
+        decode(bad_store),
+        Err(DecodeError::InvalidInstruction(bad_store))
+    );
+}
+
 #[test]
 fn decode_branch() {
     // BEQ x1, x2, 16
     let code = 0x00208863;
     assert_eq!(
         decode(code).unwrap(),
-        Instruction::BRANCH {
+        Instruction::BEQ {
+            rs1: 1,
+            rs2: 2,
+            imm: 16
+        }
+    );
+}
+
+#[test]
+fn decode_branch_conditions() {
+    assert_eq!(
+        decode(0x00209863).unwrap(),
+        Instruction::BNE {
+            rs1: 1,
+            rs2: 2,
+            imm: 16
+        }
+    );
+    assert_eq!(
+        decode(0x0020C863).unwrap(),
+        Instruction::BLT {
+            rs1: 1,
+            rs2: 2,
+            imm: 16
+        }
+    );
+    assert_eq!(
+        decode(0x0020D863).unwrap(),
+        Instruction::BGE {
+            rs1: 1,
+            rs2: 2,
+            imm: 16
+        }
+    );
+    assert_eq!(
+        decode(0x0020E863).unwrap(),
+        Instruction::BLTU {
+            rs1: 1,
+            rs2: 2,
+            imm: 16
+        }
+    );
+    assert_eq!(
+        decode(0x0020F863).unwrap(),
+        Instruction::BGEU {
             rs1: 1,
             rs2: 2,
             imm: 16
         }
     );
+    let bad_funct3 = 0x0020A863;
+    assert_eq!(
+        decode(bad_funct3),
+        Err(DecodeError::InvalidInstruction(bad_funct3))
+    );
 }
 
 #[test]
@@ -166,29 +421,70 @@ fn decode_shift_imm() {
     let slli = 0x00311093;
     assert_eq!(
         decode(slli).unwrap(),
-        Instruction::SHIFT_IMM {
+        Instruction::SLLI {
+            rd: 1,
+            rs1: 2,
+            shamt: 3
+        }
+    );
+    let srli = 0x00315093;
+    assert_eq!(
+        decode(srli).unwrap(),
+        Instruction::SRLI {
             rd: 1,
             rs1: 2,
-            shamt: 3,
-            is_srai: false
+            shamt: 3
         }
     );
-    let srai = 0x40311093;
+    let srai = 0x40315093;
     assert_eq!(
         decode(srai).unwrap(),
-        Instruction::SHIFT_IMM {
+        Instruction::SRAI {
             rd: 1,
             rs1: 2,
-            shamt: 3,
-            is_srai: true
+            shamt: 3
         }
     );
 }
 
+#[test]
+fn decode_shift_imm_reserved_bits() {
+    // SLLI with funct7 = 0x20
+    let bad_slli = 0x40311093;
+    assert_eq!(
+        decode(bad_slli),
+        Err(DecodeError::InvalidInstruction(bad_slli))
+    );
+    // SRLI with shamt[5] set, which is reserved on RV32
+    let wide_srli = 0x02315093;
+    assert_eq!(
+        decode(wide_srli),
+        Err(DecodeError::InvalidInstruction(wide_srli))
+    );
+    // SRAI with an unknown funct7
+    let bad_srai = 0xE0315093;
+    assert_eq!(
+        decode(bad_srai),
+        Err(DecodeError::InvalidInstruction(bad_srai))
+    );
+}
+
 #[test]
 fn decode_system_calls() {
     assert_eq!(decode(0x00000073).unwrap(), Instruction::ECALL);
     assert_eq!(decode(0x00100073).unwrap(), Instruction::EBREAK);
+    assert_eq!(decode(0x30200073).unwrap(), Instruction::MRET);
+    assert_eq!(decode(0x10500073).unwrap(), Instruction::WFI);
+    assert_eq!(
+        decode(0x12000073).unwrap(),
+        Instruction::SFENCE_VMA { rs1: 0, rs2: 0 }
+    );
+    assert_eq!(
+        decode(0x12B50073).unwrap(),
+        Instruction::SFENCE_VMA { rs1: 10, rs2: 11 }
+    );
+    // mret with a non-zero rd is reserved
+    assert!(decode(0x30200173).is_err());
 }
 
 #[test]
@@ -200,3 +496,799 @@ fn decode_unknown_and_invalid() {
         Err(DecodeError::InvalidInstruction(bad_sys))
     );
 }
+
+#[test]
+fn decode_compressed_expansions() {
+    // Encodings produced by `llvm-mc --triple=riscv32 -mattr=+c`.
+    let cases = [
+        (
+            0x0800,
+            Instruction::ADDI {
+                rd: 8,
+                rs1: 2,
+                imm: 16,
+            },
+        ),
+        (
+            0x41C8,
+            Instruction::LW {
+                rd: 10,
+                rs1: 11,
+                imm: 4,
+            },
+        ),
+        (
+            0xC588,
+            Instruction::SW {
+                rs1: 11,
+                rs2: 10,
+                imm: 8,
+            },
+        ),
+        (
+            0x0001,
+            Instruction::ADDI {
+                rd: 0,
+                rs1: 0,
+                imm: 0,
+            },
+        ),
+        (
+            0x1575,
+            Instruction::ADDI {
+                rd: 10,
+                rs1: 10,
+                imm: -3,
+            },
+        ),
+        (0x2801, Instruction::JAL { rd: 1, imm: 16 }),
+        (
+            0x557D,
+            Instruction::ADDI {
+                rd: 10,
+                rs1: 0,
+                imm: -1,
+            },
+        ),
+        (
+            0x7139,
+            Instruction::ADDI {
+                rd: 2,
+                rs1: 2,
+                imm: -64,
+            },
+        ),
+        (
+            0x757D,
+            Instruction::LUI {
+                rd: 10,
+                imm: 0xFFFF_F000,
+            },
+        ),
+        (
+            0x810D,
+            Instruction::SRLI {
+                rd: 10,
+                rs1: 10,
+                shamt: 3,
+            },
+        ),
+        (
+            0x850D,
+            Instruction::SRAI {
+                rd: 10,
+                rs1: 10,
+                shamt: 3,
+            },
+        ),
+        (
+            0x9979,
+            Instruction::ANDI {
+                rd: 10,
+                rs1: 10,
+                imm: -2,
+            },
+        ),
+        (
+            0x8D0D,
+            Instruction::SUB {
+                rd: 10,
+                rs1: 10,
+                rs2: 11,
+            },
+        ),
+        (
+            0x8D2D,
+            Instruction::XOR {
+                rd: 10,
+                rs1: 10,
+                rs2: 11,
+            },
+        ),
+        (
+            0x8D4D,
+            Instruction::OR {
+                rd: 10,
+                rs1: 10,
+                rs2: 11,
+            },
+        ),
+        (
+            0x8D6D,
+            Instruction::AND {
+                rd: 10,
+                rs1: 10,
+                rs2: 11,
+            },
+        ),
+        (0xBFE5, Instruction::JAL { rd: 0, imm: -8 }),
+        (
+            0xC501,
+            Instruction::BEQ {
+                rs1: 10,
+                rs2: 0,
+                imm: 8,
+            },
+        ),
+        (
+            0xFD75,
+            Instruction::BNE {
+                rs1: 10,
+                rs2: 0,
+                imm: -4,
+            },
+        ),
+        (
+            0x0512,
+            Instruction::SLLI {
+                rd: 10,
+                rs1: 10,
+                shamt: 4,
+            },
+        ),
+        (
+            0x4532,
+            Instruction::LW {
+                rd: 10,
+                rs1: 2,
+                imm: 12,
+            },
+        ),
+        (
+            0x8082,
+            Instruction::JALR {
+                rd: 0,
+                rs1: 1,
+                imm: 0,
+            },
+        ),
+        (
+            0x852E,
+            Instruction::ADD {
+                rd: 10,
+                rs1: 0,
+                rs2: 11,
+            },
+        ),
+        (0x9002, Instruction::EBREAK),
+        (
+            0x9502,
+            Instruction::JALR {
+                rd: 1,
+                rs1: 10,
+                imm: 0,
+            },
+        ),
+        (
+            0x952E,
+            Instruction::ADD {
+                rd: 10,
+                rs1: 10,
+                rs2: 11,
+            },
+        ),
+        (
+            0xCA2A,
+            Instruction::SW {
+                rs1: 2,
+                rs2: 10,
+                imm: 20,
+            },
+        ),
+    ];
+    for (half, expected) in cases {
+        assert_eq!(decode_compressed(half).unwrap(), expected, "{half:#06x}");
+    }
+}
+
+#[test]
+fn decode_compressed_reserved() {
+    // all-zero parcel, C.ADDI16SP with a zero immediate, C.LUI with a zero
+    // immediate, C.LWSP with rd = x0, C.JR with rs1 = x0, C.SRLI with
+    // shamt[5] set, and C.FLD
+    for half in [0x0000, 0x6101, 0x6501, 0x4002, 0x8002, 0x9101, 0x2000] {
+        assert_eq!(
+            decode_compressed(half),
+            Err(DecodeError::InvalidInstruction(half as u32)),
+            "{half:#06x}"
+        );
+    }
+}
+
+#[test]
+fn decode_compressed_rv64_expansions() {
+    use Instruction::*;
+    // Encodings produced by `llvm-mc --triple=riscv64 -mattr=+c`.
+    let cases = [
+        (
+            0x6588,
+            LD {
+                rd: 10,
+                rs1: 11,
+                imm: 8,
+            },
+        ),
+        (
+            0xE988,
+            SD {
+                rs1: 11,
+                rs2: 10,
+                imm: 16,
+            },
+        ),
+        (
+            0x357D,
+            ADDIW {
+                rd: 10,
+                rs1: 10,
+                imm: -1,
+            },
+        ),
+        (
+            0x9D0D,
+            SUBW {
+                rd: 10,
+                rs1: 10,
+                rs2: 11,
+            },
+        ),
+        (
+            0x9C2D,
+            ADDW {
+                rd: 8,
+                rs1: 8,
+                rs2: 11,
+            },
+        ),
+        (
+            0x1522,
+            SLLI {
+                rd: 10,
+                rs1: 10,
+                shamt: 40,
+            },
+        ),
+        (
+            0x9105,
+            SRLI {
+                rd: 10,
+                rs1: 10,
+                shamt: 33,
+            },
+        ),
+        (
+            0x957D,
+            SRAI {
+                rd: 10,
+                rs1: 10,
+                shamt: 63,
+            },
+        ),
+        (
+            0x6562,
+            LD {
+                rd: 10,
+                rs1: 2,
+                imm: 24,
+            },
+        ),
+        (
+            0xE62A,
+            SD {
+                rs1: 2,
+                rs2: 10,
+                imm: 264,
+            },
+        ),
+        // shared with RV32
+        (
+            0x4515,
+            ADDI {
+                rd: 10,
+                rs1: 0,
+                imm: 5,
+            },
+        ),
+    ];
+    for (half, expected) in cases {
+        assert_eq!(
+            decode_compressed_rv64(half).unwrap(),
+            expected,
+            "{half:#06x}"
+        );
+    }
+    // C.ADDIW and C.LDSP with rd = x0 are reserved
+    for half in [0x2001, 0x6002] {
+        assert_eq!(
+            decode_compressed_rv64(half),
+            Err(DecodeError::InvalidInstruction(half as u32)),
+            "{half:#06x}"
+        );
+    }
+}
+
+#[test]
+fn decode_is_compressed() {
+    assert!(is_compressed(0x4532));
+    assert!(!is_compressed(0x0050_0113));
+}
+
+#[test]
+fn decode_zicsr() {
+    let cases = [
+        (
+            0xC000_2573,
+            Instruction::CSRRS {
+                rd: 10,
+                rs1: 0,
+                csr: 0xC00,
+            },
+        ),
+        (
+            0xB006_15F3,
+            Instruction::CSRRW {
+                rd: 11,
+                rs1: 12,
+                csr: 0xB00,
+            },
+        ),
+        (
+            0xC021_F073,
+            Instruction::CSRRCI {
+                rd: 0,
+                uimm: 3,
+                csr: 0xC02,
+            },
+        ),
+        (
+            0x340F_D573,
+            Instruction::CSRRWI {
+                rd: 10,
+                uimm: 31,
+                csr: 0x340,
+            },
+        ),
+        (
+            0xB823_32F3,
+            Instruction::CSRRC {
+                rd: 5,
+                rs1: 6,
+                csr: 0xB82,
+            },
+        ),
+        (
+            0xC010_E573,
+            Instruction::CSRRSI {
+                rd: 10,
+                uimm: 1,
+                csr: 0xC01,
+            },
+        ),
+    ];
+    for (word, expected) in cases {
+        assert_eq!(decode(word).unwrap(), expected, "{word:#010x}");
+    }
+    let bad_funct3 = 0xC000_4573;
+    assert_eq!(
+        decode(bad_funct3),
+        Err(DecodeError::InvalidInstruction(bad_funct3))
+    );
+}
+
+#[test]
+fn decode_atomics() {
+    let (rd, rs1, rs2) = (10, 11, 12);
+    let cases = [
+        (
+            0x1005_A52F,
+            Instruction::LR_W {
+                rd,
+                rs1,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0x1405_A52F,
+            Instruction::LR_W {
+                rd,
+                rs1,
+                aq: true,
+                rl: false,
+            },
+        ),
+        (
+            0x1AC5_A52F,
+            Instruction::SC_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: true,
+            },
+        ),
+        (
+            0x08C5_A52F,
+            Instruction::AMOSWAP_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0x00C5_A52F,
+            Instruction::AMOADD_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0x20C5_A52F,
+            Instruction::AMOXOR_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0x60C5_A52F,
+            Instruction::AMOAND_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0x40C5_A52F,
+            Instruction::AMOOR_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0x80C5_A52F,
+            Instruction::AMOMIN_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0xA0C5_A52F,
+            Instruction::AMOMAX_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0xC0C5_A52F,
+            Instruction::AMOMINU_W {
+                rd,
+                rs1,
+                rs2,
+                aq: false,
+                rl: false,
+            },
+        ),
+        (
+            0xE6C5_A52F,
+            Instruction::AMOMAXU_W {
+                rd,
+                rs1,
+                rs2,
+                aq: true,
+                rl: true,
+            },
+        ),
+    ];
+    for (word, expected) in cases {
+        assert_eq!(decode(word).unwrap(), expected, "{word:#010x}");
+    }
+
+    // LR.W with rs2 != 0, a doubleword AMO and an unknown funct5
+    for word in [0x10C5_A52F, 0x00C5_B52F, 0x28C5_A52F] {
+        assert_eq!(decode(word), Err(DecodeError::InvalidInstruction(word)));
+    }
+}
+
+#[test]
+fn decode_fences() {
+    assert_eq!(
+        decode(0x0FF0_000F).unwrap(),
+        Instruction::FENCE {
+            fm: 0,
+            pred: 0xF,
+            succ: 0xF
+        }
+    );
+    assert_eq!(
+        decode(0x0310_000F).unwrap(),
+        Instruction::FENCE {
+            fm: 0,
+            pred: 0x3,
+            succ: 0x1
+        }
+    );
+    assert_eq!(
+        decode(0x8330_000F).unwrap(),
+        Instruction::FENCE {
+            fm: 0x8,
+            pred: 0x3,
+            succ: 0x3
+        }
+    );
+    // reserved rd/rs1 bits are ignored
+    assert_eq!(
+        decode(0x0FF5_8F8F).unwrap(),
+        Instruction::FENCE {
+            fm: 0,
+            pred: 0xF,
+            succ: 0xF
+        }
+    );
+    assert_eq!(decode(0x0000_100F).unwrap(), Instruction::FENCE_I);
+    let bad_funct3 = 0x0000_200F;
+    assert_eq!(
+        decode(bad_funct3),
+        Err(DecodeError::InvalidInstruction(bad_funct3))
+    );
+}
+
+#[test]
+fn decode_bitmanip() {
+    use crate::isa::IsaConfig;
+    use Instruction::*;
+    let (rd, rs1, rs2) = (10, 11, 12);
+    let cases = [
+        (0x20C5_A533, SH1ADD { rd, rs1, rs2 }),
+        (0x20C5_C533, SH2ADD { rd, rs1, rs2 }),
+        (0x20C5_E533, SH3ADD { rd, rs1, rs2 }),
+        (0x40C5_F533, ANDN { rd, rs1, rs2 }),
+        (0x40C5_E533, ORN { rd, rs1, rs2 }),
+        (0x40C5_C533, XNOR { rd, rs1, rs2 }),
+        (0x6005_9513, CLZ { rd, rs1 }),
+        (0x6015_9513, CTZ { rd, rs1 }),
+        (0x6025_9513, CPOP { rd, rs1 }),
+        (0x0AC5_E533, MAX { rd, rs1, rs2 }),
+        (0x0AC5_F533, MAXU { rd, rs1, rs2 }),
+        (0x0AC5_C533, MIN { rd, rs1, rs2 }),
+        (0x0AC5_D533, MINU { rd, rs1, rs2 }),
+        (0x6045_9513, SEXT_B { rd, rs1 }),
+        (0x6055_9513, SEXT_H { rd, rs1 }),
+        (0x0805_C533, ZEXT_H { rd, rs1 }),
+        (0x60C5_9533, ROL { rd, rs1, rs2 }),
+        (0x60C5_D533, ROR { rd, rs1, rs2 }),
+        (0x6075_D513, RORI { rd, rs1, shamt: 7 }),
+        (0x2875_D513, ORC_B { rd, rs1 }),
+        (0x6985_D513, REV8 { rd, rs1 }),
+        (0x48C5_9533, BCLR { rd, rs1, rs2 }),
+        (0x4835_9513, BCLRI { rd, rs1, shamt: 3 }),
+        (0x48C5_D533, BEXT { rd, rs1, rs2 }),
+        (0x4835_D513, BEXTI { rd, rs1, shamt: 3 }),
+        (0x68C5_9533, BINV { rd, rs1, rs2 }),
+        (0x6835_9513, BINVI { rd, rs1, shamt: 3 }),
+        (0x28C5_9533, BSET { rd, rs1, rs2 }),
+        (0x29F5_9513, BSETI { rd, rs1, shamt: 31 }),
+    ];
+    for (word, expected) in cases {
+        assert_eq!(decode(word).unwrap(), expected, "{word:#010x}");
+        assert_eq!(
+            decode_with(word, IsaConfig::rv32im()),
+            Err(DecodeError::ExtensionDisabled(word)),
+            "{word:#010x}"
+        );
+    }
+
+    // each extension can be switched off on its own
+    let no_zba = IsaConfig {
+        zba: false,
+        ..IsaConfig::default()
+    };
+    assert!(decode_with(0x20C5_A533, no_zba).is_err());
+    assert!(decode_with(0x40C5_F533, no_zba).is_ok());
+    // clz with an unused rs2 selector is not an instruction
+    assert!(decode(0x6035_9513).is_err());
+}
+
+#[test]
+fn decode_respects_isa_profile() {
+    use crate::isa::IsaConfig;
+    let rv32i = IsaConfig::rv32i();
+    let words = [
+        0x02C5_8533, // mul a0, a1, a2
+        0x02C5_C533, // div a0, a1, a2
+        0x1005_A52F, // lr.w a0, (a1)
+        0xC000_2573, // rdcycle a0
+        0xC000_6573, // csrrsi a0, cycle, 0
+    ];
+    for word in words {
+        assert!(decode(word).is_ok(), "{word:#010x}");
+        assert_eq!(
+            decode_with(word, rv32i),
+            Err(DecodeError::ExtensionDisabled(word)),
+            "{word:#010x}"
+        );
+    }
+    assert!(decode_with(0x02C5_8533, IsaConfig::rv32im()).is_ok());
+    // the base ISA and ecall are always available
+    assert!(decode_with(0x00C5_8533, rv32i).is_ok());
+    assert_eq!(decode_with(0x0000_0073, rv32i), Ok(Instruction::ECALL));
+
+    assert!(decode_compressed_with(0x4505, IsaConfig::default()).is_ok());
+    assert_eq!(
+        decode_compressed_with(0x4505, rv32i),
+        Err(DecodeError::ExtensionDisabled(0x4505))
+    );
+    // reserved encodings stay invalid whatever the profile
+    assert_eq!(
+        decode_with(0x0000_0000, rv32i),
+        Err(DecodeError::UnknownOpcode(0))
+    );
+    assert_eq!(
+        decode_compressed_with(0x0000, rv32i),
+        Err(DecodeError::InvalidInstruction(0))
+    );
+}
+
+#[test]
+fn decode_rv64_instructions() {
+    use Instruction::*;
+    let (rd, rs1, rs2) = (10, 11, 12);
+    let cases = [
+        (
+            0x0400_3683,
+            LD {
+                rd: 13,
+                rs1: 0,
+                imm: 64,
+            },
+        ),
+        (
+            0x0400_6703,
+            LWU {
+                rd: 14,
+                rs1: 0,
+                imm: 64,
+            },
+        ),
+        (
+            0x04B0_3023,
+            SD {
+                rs1: 0,
+                rs2: 11,
+                imm: 64,
+            },
+        ),
+        (
+            0x0285_9293,
+            SLLI {
+                rd: 5,
+                rs1: 11,
+                shamt: 40,
+            },
+        ),
+        (
+            0x43F2_D313,
+            SRAI {
+                rd: 6,
+                rs1: 5,
+                shamt: 63,
+            },
+        ),
+        (
+            0x0015_841B,
+            ADDIW {
+                rd: 8,
+                rs1: 11,
+                imm: 1,
+            },
+        ),
+        (0x01F5_951B, SLLIW { rd, rs1, shamt: 31 }),
+        (0x01F5_D51B, SRLIW { rd, rs1, shamt: 31 }),
+        (
+            0x41F5_549B,
+            SRAIW {
+                rd: 9,
+                rs1: 10,
+                shamt: 31,
+            },
+        ),
+        (
+            0x00B5_863B,
+            ADDW {
+                rd: 12,
+                rs1: 11,
+                rs2: 11,
+            },
+        ),
+        (
+            0x40B0_03BB,
+            SUBW {
+                rd: 7,
+                rs1: 0,
+                rs2: 11,
+            },
+        ),
+        (0x00C5_953B, SLLW { rd, rs1, rs2 }),
+        (0x00C5_D53B, SRLW { rd, rs1, rs2 }),
+        (0x40C5_D53B, SRAW { rd, rs1, rs2 }),
+        (0x02B5_853B, MULW { rd, rs1, rs2: 11 }),
+        (0x02C5_C53B, DIVW { rd, rs1, rs2 }),
+        (0x02C5_D53B, DIVUW { rd, rs1, rs2 }),
+        (0x02C5_E53B, REMW { rd, rs1, rs2 }),
+        (0x02C5_F53B, REMUW { rd, rs1, rs2 }),
+        // shared with RV32
+        (0x00C5_8533, ADD { rd, rs1, rs2 }),
+        (0x0000_0073, ECALL),
+    ];
+    for (word, expected) in cases {
+        assert_eq!(decode_rv64(word).unwrap(), expected, "{word:#010x}");
+    }
+
+    // the RV64-only opcodes are not part of RV32
+    assert_eq!(decode(0x00B5_863B), Err(DecodeError::UnknownOpcode(0x3B)));
+    assert_eq!(decode(0x0015_841B), Err(DecodeError::UnknownOpcode(0x1B)));
+    assert_eq!(
+        decode(0x0400_3683),
+        Err(DecodeError::InvalidInstruction(0x0400_3683))
+    );
+    // shamt[5] of a W shift is reserved
+    assert!(decode_rv64(0x0215_951B).is_err());
+
+    // the word-sized M ops need M, and the other extensions apply as on RV32
+    let base = IsaConfig::rv32i();
+    assert_eq!(
+        decode_rv64_with(0x02B5_853B, base),
+        Err(DecodeError::ExtensionDisabled(0x02B5_853B))
+    );
+    assert_eq!(
+        decode_rv64_with(0x0000_0013, base),
+        Ok(ADDI {
+            rd: 0,
+            rs1: 0,
+            imm: 0
+        })
+    );
+    assert_eq!(
+        decode_rv64_with(0xC000_2573, IsaConfig::rv32im()),
+        Err(DecodeError::ExtensionDisabled(0xC000_2573))
+    );
+    assert!(decode_rv64(0xC000_2573).is_ok());
+}
@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests;
+
+use std::{fmt, path::Path};
+
+use crate::{
+    compiler::{ElfLoader, errors::ElfError},
+    decoder::{decode, decode_compressed, decode_compressed_rv64, decode_rv64, is_compressed},
+    itxs::Instruction,
+    memory::{Memory, errors::MemoryError},
+};
+
+/// One disassembled instruction parcel.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub addr: u32,
+    /// The encoding, 2 or 4 bytes long as given by `len`.
+    pub raw: u32,
+    pub len: u32,
+    /// `None` when `raw` isn't a valid encoding.
+    pub instr: Option<Instruction>,
+}
+
+/// Formats as `addr: encoding  assembly`, e.g.
+/// `00000010:  00a12623    sw a0, 12(sp)`. Branch and jump targets are
+/// absolute addresses, and invalid encodings are shown as `.half`/`.word`
+/// data directives.
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}:  ", self.addr)?;
+        match (self.len, &self.instr) {
+            (2, Some(instr)) => write!(f, "{:04x}        {}", self.raw, instr.at(self.addr)),
+            (2, None) => write!(f, "{:04x}        .half {:#06x}", self.raw, self.raw),
+            (_, Some(instr)) => write!(f, "{:08x}    {}", self.raw, instr.at(self.addr)),
+            (_, None) => write!(f, "{:08x}    .word {:#010x}", self.raw, self.raw),
+        }
+    }
+}
+
+/// Disassembles the RV32 code in `start..end` of `memory`, including
+/// compressed instructions.
+pub fn disassemble(memory: &Memory, start: u32, end: u32) -> Result<Vec<Line>, MemoryError> {
+    let bytes = (start..end)
+        .map(|addr| memory.load_byte(addr))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(disassemble_bytes(&bytes, start, false))
+}
+
+/// Disassembles the `.text` section of the ELF at `path`, as RV64 code for
+/// an ELFCLASS64 image and RV32 otherwise.
+pub fn disassemble_elf<P: AsRef<Path>>(path: P) -> Result<Vec<Line>, ElfError> {
+    let text = ElfLoader::text_section(path)?;
+    Ok(disassemble_bytes(&text.bytes, text.addr, text.is_64))
+}
+
+/// Disassembles `bytes` as code starting at `base`, as RV64 code when
+/// `is_64` is set. Compressed instructions are listed for RV64 too, even
+/// though the RV64 hart cannot run them.
+pub fn disassemble_bytes(bytes: &[u8], base: u32, is_64: bool) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset + 2 <= bytes.len() {
+        let addr = base.wrapping_add(offset as u32);
+        let half = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let line = if is_compressed(half as u32) {
+            let instr = if is_64 {
+                decode_compressed_rv64(half)
+            } else {
+                decode_compressed(half)
+            };
+            Line {
+                addr,
+                raw: half as u32,
+                len: 2,
+                instr: instr.ok(),
+            }
+        } else if let Some(word) = bytes.get(offset..offset + 4) {
+            let raw = u32::from_le_bytes(word.try_into().unwrap());
+            let instr = if is_64 { decode_rv64(raw) } else { decode(raw) };
+            Line {
+                addr,
+                raw,
+                len: 4,
+                instr: instr.ok(),
+            }
+        } else {
+            // the first half of a 32-bit instruction cut off by the end
+            Line {
+                addr,
+                raw: half as u32,
+                len: 2,
+                instr: None,
+            }
+        };
+        offset += line.len as usize;
+        lines.push(line);
+    }
+    lines
+}
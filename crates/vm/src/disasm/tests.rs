@@ -0,0 +1,192 @@
+use super::*;
+use goblin::elf::header::{ELFCLASS32, ELFDATA2LSB};
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+fn listing(lines: &[Line]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+#[test]
+fn test_disassemble_memory_range() {
+    let mut memory = Memory::new(128).unwrap();
+    let bytes = [
+        0x23, 0x26, 0xA1, 0x00, // sw a0, 12(sp)
+        0x15, 0x41, // c.li sp, 5
+        0x82, 0x80, // c.jr ra
+        0xFF, 0xFF, 0xFF, 0xFF, // not an instruction
+        0x13, 0x00, // first half of a 32-bit instruction
+    ];
+    for (i, byte) in bytes.iter().enumerate() {
+        memory.store_byte(0x20 + i as u32, *byte).unwrap();
+    }
+    let lines = disassemble(&memory, 0x20, 0x20 + bytes.len() as u32).unwrap();
+    assert_eq!(
+        listing(&lines),
+        [
+            "00000020:  00a12623    sw a0, 12(sp)",
+            "00000024:  4115        li sp, 5",
+            "00000026:  8082        ret",
+            "00000028:  ffffffff    .word 0xffffffff",
+            "0000002c:  0013        .half 0x0013",
+        ]
+    );
+    assert_eq!(
+        disassemble(&memory, 120, 130),
+        Err(MemoryError::OutOfBounds {
+            addr: 128,
+            size: 128
+        })
+    );
+}
+
+#[test]
+fn test_disassemble_rv64_words() {
+    let bytes = [
+        0x03, 0x35, 0x81, 0x00, // ld a0, 8(sp)
+        0x1B, 0x85, 0x05, 0x00, // sext.w a0, a1
+    ];
+    let lines = disassemble_bytes(&bytes, 0x100, true);
+    assert_eq!(
+        listing(&lines),
+        [
+            "00000100:  00813503    ld a0, 8(sp)",
+            "00000104:  0005851b    sext.w a0, a1",
+        ]
+    );
+}
+
+#[test]
+fn test_disassemble_branch_targets() {
+    let bytes = [
+        0x63, 0x14, 0xB5, 0x00, // bne a0, a1, 8
+        0xF5, 0xBF, // c.j -4
+        0x09, 0x28, // c.jal 18
+        0xE3, 0x0D, 0xB5, 0xFE, // beq a0, a1, -6
+    ];
+    let lines = disassemble_bytes(&bytes, 0x1000, false);
+    assert_eq!(
+        listing(&lines),
+        [
+            "00001000:  00b51463    bne a0, a1, 0x1008",
+            "00001004:  bff5        j 0x1000",
+            "00001006:  2809        jal 0x1018",
+            "00001008:  feb50de3    beq a0, a1, 0x1002",
+        ]
+    );
+    // the instruction on its own still shows the offset
+    assert_eq!(lines[1].instr.as_ref().unwrap().to_string(), "j -4");
+}
+
+#[test]
+fn test_disassemble_rv64_compressed() {
+    let bytes = [
+        0x88, 0x65, // c.ld a0, 8(a1)
+        0x1B, 0x85, 0x05, 0x00, // sext.w a0, a1
+        0x7D, 0x35, // c.addiw a0, -1
+        0x03, 0x35, 0x81, 0x00, // ld a0, 8(sp)
+        0x82, 0x80, // c.jr ra
+    ];
+    let lines = disassemble_bytes(&bytes, 0x100, true);
+    assert_eq!(
+        listing(&lines),
+        [
+            "00000100:  6588        ld a0, 8(a1)",
+            "00000102:  0005851b    sext.w a0, a1",
+            "00000106:  357d        addiw a0, a0, -1",
+            "00000108:  00813503    ld a0, 8(sp)",
+            "0000010c:  8082        ret",
+        ]
+    );
+}
+
+/// An ELF32 with no segments and a `.text` section at 0x1000 holding `text`.
+fn elf_with_text(text: &[u8]) -> NamedTempFile {
+    let strtab = b"\0.text\0.shstrtab\0";
+    let text_off = 52;
+    let strtab_off = text_off + text.len();
+    let shoff = (strtab_off + strtab.len()).next_multiple_of(4);
+
+    let mut elf = vec![0u8; shoff];
+    elf[0..4].copy_from_slice(&[0x7F, b'E', b'L', b'F']);
+    elf[4] = ELFCLASS32;
+    elf[5] = ELFDATA2LSB;
+    elf[6] = 1;
+    elf[16..18].copy_from_slice(&2u16.to_le_bytes());
+    elf[18..20].copy_from_slice(&0xF3u16.to_le_bytes());
+    elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+    elf[24..28].copy_from_slice(&0x1000u32.to_le_bytes());
+    elf[32..36].copy_from_slice(&(shoff as u32).to_le_bytes());
+    elf[40..42].copy_from_slice(&52u16.to_le_bytes());
+    elf[46..48].copy_from_slice(&40u16.to_le_bytes());
+    elf[48..50].copy_from_slice(&3u16.to_le_bytes());
+    elf[50..52].copy_from_slice(&2u16.to_le_bytes());
+    elf[text_off..strtab_off].copy_from_slice(text);
+    elf[strtab_off..strtab_off + strtab.len()].copy_from_slice(strtab);
+
+    // null, .text and .shstrtab section headers
+    let sections: [[u32; 10]; 3] = [
+        [0; 10],
+        [
+            1,
+            1,
+            0x6,
+            0x1000,
+            text_off as u32,
+            text.len() as u32,
+            0,
+            0,
+            4,
+            0,
+        ],
+        [
+            7,
+            3,
+            0,
+            0,
+            strtab_off as u32,
+            strtab.len() as u32,
+            0,
+            0,
+            1,
+            0,
+        ],
+    ];
+    for field in sections.iter().flatten() {
+        elf.extend_from_slice(&field.to_le_bytes());
+    }
+
+    let mut tmp = NamedTempFile::new().unwrap();
+    tmp.write_all(&elf).unwrap();
+    tmp
+}
+
+#[test]
+fn test_disassemble_elf_text() {
+    let text = [
+        0x13, 0x01, 0x01, 0xFF, // addi sp, sp, -16
+        0x73, 0x00, 0x00, 0x00, // ecall
+    ];
+    let elf = elf_with_text(&text);
+    let lines = disassemble_elf(elf.path()).unwrap();
+    assert_eq!(
+        listing(&lines),
+        [
+            "00001000:  ff010113    addi sp, sp, -16",
+            "00001004:  00000073    ecall",
+        ]
+    );
+}
+
+#[test]
+fn test_disassemble_elf_without_text() {
+    let mut header = [0u8; 52];
+    header[0..4].copy_from_slice(&[0x7F, b'E', b'L', b'F']);
+    header[4] = ELFCLASS32;
+    header[5] = ELFDATA2LSB;
+    let mut tmp = NamedTempFile::new().unwrap();
+    tmp.write_all(&header).unwrap();
+
+    let err = disassemble_elf(tmp.path()).unwrap_err();
+    assert!(matches!(err, ElfError::MissingSection(".text")));
+}
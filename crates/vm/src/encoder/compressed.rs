@@ -0,0 +1,190 @@
+use crate::itxs::Instruction;
+
+const REG_RA: u8 = 1;
+const REG_SP: u8 = 2;
+
+/// Encodes `instr` as the 16-bit RVC instruction that expands to it, if
+/// there is one. Where several compressed forms expand to the same
+/// instruction, any of them may be chosen.
+pub fn encode_compressed(instr: &Instruction) -> Option<u16> {
+    use Instruction::*;
+    let half = match *instr {
+        // C.ADDI4SPN
+        ADDI { rd, rs1, imm }
+            if rs1 == REG_SP && is_creg(rd) && imm > 0 && fits_unsigned(imm, 10, 4) =>
+        {
+            let imm = imm as u32;
+            cbits(imm, 3, 5)
+                | cbits(imm, 2, 6)
+                | (((imm >> 6) & 0xF) << 7)
+                | (((imm >> 4) & 0x3) << 11)
+                | (creg(rd) << 2)
+        }
+        // C.ADDI16SP
+        ADDI { rd, rs1, imm }
+            if rd == REG_SP && rs1 == REG_SP && imm != 0 && fits_signed(imm, 10, 16) =>
+        {
+            let imm = imm as u32;
+            (0x3 << 13)
+                | cbits(imm, 9, 12)
+                | cbits(imm, 4, 6)
+                | cbits(imm, 6, 5)
+                | (((imm >> 7) & 0x3) << 3)
+                | cbits(imm, 5, 2)
+                | ((REG_SP as u32) << 7)
+                | 0x1
+        }
+        // C.NOP / C.ADDI
+        ADDI { rd, rs1, imm } if rd == rs1 && fits_signed(imm, 6, 1) => ci_type(0x0, rd, imm) | 0x1,
+        // C.LI
+        ADDI { rd, rs1: 0, imm } if fits_signed(imm, 6, 1) => ci_type(0x2, rd, imm) | 0x1,
+        // C.LUI
+        LUI { rd, imm }
+            if rd != REG_SP
+                && imm != 0
+                && imm & 0xFFF == 0
+                && fits_signed(imm as i32 >> 12, 6, 1) =>
+        {
+            ci_type(0x3, rd, imm as i32 >> 12) | 0x1
+        }
+        // C.LW
+        LW { rd, rs1, imm } if is_creg(rd) && is_creg(rs1) && fits_unsigned(imm, 7, 4) => {
+            cl_type(0x2, rs1, rd, imm)
+        }
+        // C.LWSP
+        LW { rd, rs1, imm } if rd != 0 && rs1 == REG_SP && fits_unsigned(imm, 8, 4) => {
+            let imm = imm as u32;
+            (0x2 << 13)
+                | cbits(imm, 5, 12)
+                | (((imm >> 2) & 0x7) << 4)
+                | (((imm >> 6) & 0x3) << 2)
+                | ((rd as u32) << 7)
+                | 0x2
+        }
+        // C.SW
+        SW { rs1, rs2, imm } if is_creg(rs1) && is_creg(rs2) && fits_unsigned(imm, 7, 4) => {
+            cl_type(0x6, rs1, rs2, imm)
+        }
+        // C.SWSP
+        SW { rs1, rs2, imm } if rs1 == REG_SP && rs2 < 32 && fits_unsigned(imm, 8, 4) => {
+            let imm = imm as u32;
+            (0x6 << 13)
+                | (((imm >> 2) & 0xF) << 9)
+                | (((imm >> 6) & 0x3) << 7)
+                | ((rs2 as u32) << 2)
+                | 0x2
+        }
+        // C.JAL / C.J
+        JAL { rd, imm } if (rd == REG_RA || rd == 0) && fits_signed(imm, 12, 2) => {
+            let funct3 = if rd == REG_RA { 0x1 } else { 0x5 };
+            let imm = imm as u32;
+            (funct3 << 13)
+                | cbits(imm, 11, 12)
+                | cbits(imm, 4, 11)
+                | (((imm >> 8) & 0x3) << 9)
+                | cbits(imm, 10, 8)
+                | cbits(imm, 6, 7)
+                | cbits(imm, 7, 6)
+                | (((imm >> 1) & 0x7) << 3)
+                | cbits(imm, 5, 2)
+                | 0x1
+        }
+        // C.BEQZ / C.BNEZ
+        BEQ { rs1, rs2: 0, imm } if is_creg(rs1) && fits_signed(imm, 9, 2) => {
+            cb_branch(0x6, rs1, imm)
+        }
+        BNE { rs1, rs2: 0, imm } if is_creg(rs1) && fits_signed(imm, 9, 2) => {
+            cb_branch(0x7, rs1, imm)
+        }
+        // C.SLLI
+        SLLI { rd, rs1, shamt } if rd == rs1 && rd < 32 && shamt < 32 => {
+            ((rd as u32) << 7) | ((shamt as u32) << 2) | 0x2
+        }
+        // C.SRLI / C.SRAI / C.ANDI
+        SRLI { rd, rs1, shamt } if rd == rs1 && is_creg(rd) && shamt < 32 => {
+            cb_alu(0x0, rd, shamt as u32)
+        }
+        SRAI { rd, rs1, shamt } if rd == rs1 && is_creg(rd) && shamt < 32 => {
+            cb_alu(0x1, rd, shamt as u32)
+        }
+        ANDI { rd, rs1, imm } if rd == rs1 && is_creg(rd) && fits_signed(imm, 6, 1) => {
+            cb_alu(0x2, rd, imm as u32)
+        }
+        // C.SUB / C.XOR / C.OR / C.AND
+        SUB { rd, rs1, rs2 } if rd == rs1 && is_creg(rd) && is_creg(rs2) => ca_type(0x0, rd, rs2),
+        XOR { rd, rs1, rs2 } if rd == rs1 && is_creg(rd) && is_creg(rs2) => ca_type(0x1, rd, rs2),
+        OR { rd, rs1, rs2 } if rd == rs1 && is_creg(rd) && is_creg(rs2) => ca_type(0x2, rd, rs2),
+        AND { rd, rs1, rs2 } if rd == rs1 && is_creg(rd) && is_creg(rs2) => ca_type(0x3, rd, rs2),
+        // C.JR / C.JALR
+        JALR { rd, rs1, imm: 0 } if (rd == 0 || rd == REG_RA) && rs1 != 0 && rs1 < 32 => {
+            let bit12 = if rd == REG_RA { 1 << 12 } else { 0 };
+            (0x4 << 13) | bit12 | ((rs1 as u32) << 7) | 0x2
+        }
+        // C.MV / C.ADD
+        ADD { rd, rs1, rs2 } if (rs1 == 0 || rs1 == rd) && rd < 32 && rs2 != 0 && rs2 < 32 => {
+            let bit12 = if rs1 == 0 { 0 } else { 1 << 12 };
+            (0x4 << 13) | bit12 | ((rd as u32) << 7) | ((rs2 as u32) << 2) | 0x2
+        }
+        EBREAK => 0x9002,
+        _ => return None,
+    };
+    Some(half as u16)
+}
+
+fn is_creg(r: u8) -> bool {
+    (8..16).contains(&r)
+}
+
+fn creg(r: u8) -> u32 {
+    (r - 8) as u32
+}
+
+fn cbits(imm: u32, from: u32, to: u32) -> u32 {
+    ((imm >> from) & 0x1) << to
+}
+
+/// Whether `imm` is a multiple of `align` that fits in `bits` unsigned bits.
+fn fits_unsigned(imm: i32, bits: u32, align: i32) -> bool {
+    imm >= 0 && imm < (1 << bits) && imm % align == 0
+}
+
+/// Whether `imm` is a multiple of `align` that fits in `bits` signed bits.
+fn fits_signed(imm: i32, bits: u32, align: i32) -> bool {
+    let half = 1 << (bits - 1);
+    (-half..half).contains(&imm) && imm % align == 0
+}
+
+fn ci_type(funct3: u32, rd: u8, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (funct3 << 13) | cbits(imm, 5, 12) | (((rd & 0x1F) as u32) << 7) | ((imm & 0x1F) << 2)
+}
+
+fn cl_type(funct3: u32, rs1: u8, rd_rs2: u8, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (funct3 << 13)
+        | (((imm >> 3) & 0x7) << 10)
+        | (creg(rs1) << 7)
+        | cbits(imm, 2, 6)
+        | cbits(imm, 6, 5)
+        | (creg(rd_rs2) << 2)
+}
+
+fn cb_branch(funct3: u32, rs1: u8, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (funct3 << 13)
+        | cbits(imm, 8, 12)
+        | (((imm >> 3) & 0x3) << 10)
+        | (creg(rs1) << 7)
+        | (((imm >> 6) & 0x3) << 5)
+        | (((imm >> 1) & 0x3) << 3)
+        | cbits(imm, 5, 2)
+        | 0x1
+}
+
+fn cb_alu(funct2: u32, rd: u8, imm: u32) -> u32 {
+    (0x4 << 13) | cbits(imm, 5, 12) | (funct2 << 10) | (creg(rd) << 7) | ((imm & 0x1F) << 2) | 0x1
+}
+
+fn ca_type(funct2: u32, rd: u8, rs2: u8) -> u32 {
+    (0x4 << 13) | (0x3 << 10) | (creg(rd) << 7) | (funct2 << 5) | (creg(rs2) << 2) | 0x1
+}
@@ -0,0 +1,272 @@
+mod compressed;
+#[cfg(test)]
+mod tests;
+
+use crate::itxs::Instruction;
+
+pub use compressed::encode_compressed;
+
+/// Encodes `instr` as a 32-bit instruction word, the inverse of
+/// [`crate::decoder::decode`] (and [`crate::decoder::decode_rv64`] for the
+/// RV64 instructions).
+///
+/// Fields are expected to be in range for their encoding, as the decoder
+/// produces them; bits that don't fit are dropped. The reserved fields of
+/// FENCE and FENCE.I are encoded as zero.
+pub fn encode(instr: &Instruction) -> u32 {
+    use Instruction::*;
+    match *instr {
+        LUI { rd, imm } => u_type(0x37, rd, imm),
+        AUIPC { rd, imm } => u_type(0x17, rd, imm),
+        JAL { rd, imm } => j_type(0x6F, rd, imm),
+        JALR { rd, rs1, imm } => i_type(0x67, 0x0, rd, rs1, imm),
+
+        LB { rd, rs1, imm } => i_type(0x03, 0x0, rd, rs1, imm),
+        LH { rd, rs1, imm } => i_type(0x03, 0x1, rd, rs1, imm),
+        LW { rd, rs1, imm } => i_type(0x03, 0x2, rd, rs1, imm),
+        LD { rd, rs1, imm } => i_type(0x03, 0x3, rd, rs1, imm),
+        LBU { rd, rs1, imm } => i_type(0x03, 0x4, rd, rs1, imm),
+        LHU { rd, rs1, imm } => i_type(0x03, 0x5, rd, rs1, imm),
+        LWU { rd, rs1, imm } => i_type(0x03, 0x6, rd, rs1, imm),
+
+        ADDI { rd, rs1, imm } => i_type(0x13, 0x0, rd, rs1, imm),
+        SLTI { rd, rs1, imm } => i_type(0x13, 0x2, rd, rs1, imm),
+        SLTIU { rd, rs1, imm } => i_type(0x13, 0x3, rd, rs1, imm),
+        XORI { rd, rs1, imm } => i_type(0x13, 0x4, rd, rs1, imm),
+        ORI { rd, rs1, imm } => i_type(0x13, 0x6, rd, rs1, imm),
+        ANDI { rd, rs1, imm } => i_type(0x13, 0x7, rd, rs1, imm),
+        // RV64 shift amounts spill into bit 25, the low bit of funct7
+        SLLI { rd, rs1, shamt } => shift_type(0x13, 0x1, 0x00, rd, rs1, shamt & 0x3F),
+        SRLI { rd, rs1, shamt } => shift_type(0x13, 0x5, 0x00, rd, rs1, shamt & 0x3F),
+        SRAI { rd, rs1, shamt } => shift_type(0x13, 0x5, 0x20, rd, rs1, shamt & 0x3F),
+
+        SB { rs1, rs2, imm } => s_type(0x23, 0x0, rs1, rs2, imm),
+        SH { rs1, rs2, imm } => s_type(0x23, 0x1, rs1, rs2, imm),
+        SW { rs1, rs2, imm } => s_type(0x23, 0x2, rs1, rs2, imm),
+        SD { rs1, rs2, imm } => s_type(0x23, 0x3, rs1, rs2, imm),
+
+        BEQ { rs1, rs2, imm } => b_type(0x0, rs1, rs2, imm),
+        BNE { rs1, rs2, imm } => b_type(0x1, rs1, rs2, imm),
+        BLT { rs1, rs2, imm } => b_type(0x4, rs1, rs2, imm),
+        BGE { rs1, rs2, imm } => b_type(0x5, rs1, rs2, imm),
+        BLTU { rs1, rs2, imm } => b_type(0x6, rs1, rs2, imm),
+        BGEU { rs1, rs2, imm } => b_type(0x7, rs1, rs2, imm),
+
+        ADD { rd, rs1, rs2 } => r_type(0x33, 0x0, 0x00, rd, rs1, rs2),
+        SUB { rd, rs1, rs2 } => r_type(0x33, 0x0, 0x20, rd, rs1, rs2),
+        SLL { rd, rs1, rs2 } => r_type(0x33, 0x1, 0x00, rd, rs1, rs2),
+        SLT { rd, rs1, rs2 } => r_type(0x33, 0x2, 0x00, rd, rs1, rs2),
+        SLTU { rd, rs1, rs2 } => r_type(0x33, 0x3, 0x00, rd, rs1, rs2),
+        XOR { rd, rs1, rs2 } => r_type(0x33, 0x4, 0x00, rd, rs1, rs2),
+        SRL { rd, rs1, rs2 } => r_type(0x33, 0x5, 0x00, rd, rs1, rs2),
+        SRA { rd, rs1, rs2 } => r_type(0x33, 0x5, 0x20, rd, rs1, rs2),
+        OR { rd, rs1, rs2 } => r_type(0x33, 0x6, 0x00, rd, rs1, rs2),
+        AND { rd, rs1, rs2 } => r_type(0x33, 0x7, 0x00, rd, rs1, rs2),
+
+        FENCE { fm, pred, succ } => {
+            (((fm & 0xF) as u32) << 28)
+                | (((pred & 0xF) as u32) << 24)
+                | (((succ & 0xF) as u32) << 20)
+                | 0x0F
+        }
+        FENCE_I => 0x0000_100F,
+        ECALL => 0x0000_0073,
+        EBREAK => 0x0010_0073,
+        MRET => 0x3020_0073,
+        WFI => 0x1050_0073,
+        SFENCE_VMA { rs1, rs2 } => r_type(0x73, 0x0, 0x09, 0, rs1, rs2),
+
+        LR_W { rd, rs1, aq, rl } => amo_type(0x02, rd, rs1, 0, aq, rl),
+        SC_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x03, rd, rs1, rs2, aq, rl),
+        AMOSWAP_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x01, rd, rs1, rs2, aq, rl),
+        AMOADD_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x00, rd, rs1, rs2, aq, rl),
+        AMOXOR_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x04, rd, rs1, rs2, aq, rl),
+        AMOAND_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x0C, rd, rs1, rs2, aq, rl),
+        AMOOR_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x08, rd, rs1, rs2, aq, rl),
+        AMOMIN_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x10, rd, rs1, rs2, aq, rl),
+        AMOMAX_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x14, rd, rs1, rs2, aq, rl),
+        AMOMINU_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x18, rd, rs1, rs2, aq, rl),
+        AMOMAXU_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        } => amo_type(0x1C, rd, rs1, rs2, aq, rl),
+
+        CSRRW { rd, rs1, csr } => csr_type(0x1, rd, rs1, csr),
+        CSRRS { rd, rs1, csr } => csr_type(0x2, rd, rs1, csr),
+        CSRRC { rd, rs1, csr } => csr_type(0x3, rd, rs1, csr),
+        CSRRWI { rd, uimm, csr } => csr_type(0x5, rd, uimm, csr),
+        CSRRSI { rd, uimm, csr } => csr_type(0x6, rd, uimm, csr),
+        CSRRCI { rd, uimm, csr } => csr_type(0x7, rd, uimm, csr),
+
+        MUL { rd, rs1, rs2 } => r_type(0x33, 0x0, 0x01, rd, rs1, rs2),
+        MULH { rd, rs1, rs2 } => r_type(0x33, 0x1, 0x01, rd, rs1, rs2),
+        MULHSU { rd, rs1, rs2 } => r_type(0x33, 0x2, 0x01, rd, rs1, rs2),
+        MULHU { rd, rs1, rs2 } => r_type(0x33, 0x3, 0x01, rd, rs1, rs2),
+        DIV { rd, rs1, rs2 } => r_type(0x33, 0x4, 0x01, rd, rs1, rs2),
+        DIVU { rd, rs1, rs2 } => r_type(0x33, 0x5, 0x01, rd, rs1, rs2),
+        REM { rd, rs1, rs2 } => r_type(0x33, 0x6, 0x01, rd, rs1, rs2),
+        REMU { rd, rs1, rs2 } => r_type(0x33, 0x7, 0x01, rd, rs1, rs2),
+
+        SH1ADD { rd, rs1, rs2 } => r_type(0x33, 0x2, 0x10, rd, rs1, rs2),
+        SH2ADD { rd, rs1, rs2 } => r_type(0x33, 0x4, 0x10, rd, rs1, rs2),
+        SH3ADD { rd, rs1, rs2 } => r_type(0x33, 0x6, 0x10, rd, rs1, rs2),
+
+        ANDN { rd, rs1, rs2 } => r_type(0x33, 0x7, 0x20, rd, rs1, rs2),
+        ORN { rd, rs1, rs2 } => r_type(0x33, 0x6, 0x20, rd, rs1, rs2),
+        XNOR { rd, rs1, rs2 } => r_type(0x33, 0x4, 0x20, rd, rs1, rs2),
+        MIN { rd, rs1, rs2 } => r_type(0x33, 0x4, 0x05, rd, rs1, rs2),
+        MINU { rd, rs1, rs2 } => r_type(0x33, 0x5, 0x05, rd, rs1, rs2),
+        MAX { rd, rs1, rs2 } => r_type(0x33, 0x6, 0x05, rd, rs1, rs2),
+        MAXU { rd, rs1, rs2 } => r_type(0x33, 0x7, 0x05, rd, rs1, rs2),
+        ROL { rd, rs1, rs2 } => r_type(0x33, 0x1, 0x30, rd, rs1, rs2),
+        ROR { rd, rs1, rs2 } => r_type(0x33, 0x5, 0x30, rd, rs1, rs2),
+        CLZ { rd, rs1 } => r_type(0x13, 0x1, 0x30, rd, rs1, 0x00),
+        CTZ { rd, rs1 } => r_type(0x13, 0x1, 0x30, rd, rs1, 0x01),
+        CPOP { rd, rs1 } => r_type(0x13, 0x1, 0x30, rd, rs1, 0x02),
+        SEXT_B { rd, rs1 } => r_type(0x13, 0x1, 0x30, rd, rs1, 0x04),
+        SEXT_H { rd, rs1 } => r_type(0x13, 0x1, 0x30, rd, rs1, 0x05),
+        ZEXT_H { rd, rs1 } => r_type(0x33, 0x4, 0x04, rd, rs1, 0x00),
+        ORC_B { rd, rs1 } => r_type(0x13, 0x5, 0x14, rd, rs1, 0x07),
+        REV8 { rd, rs1 } => r_type(0x13, 0x5, 0x34, rd, rs1, 0x18),
+        RORI { rd, rs1, shamt } => r_type(0x13, 0x5, 0x30, rd, rs1, shamt),
+
+        BCLR { rd, rs1, rs2 } => r_type(0x33, 0x1, 0x24, rd, rs1, rs2),
+        BEXT { rd, rs1, rs2 } => r_type(0x33, 0x5, 0x24, rd, rs1, rs2),
+        BINV { rd, rs1, rs2 } => r_type(0x33, 0x1, 0x34, rd, rs1, rs2),
+        BSET { rd, rs1, rs2 } => r_type(0x33, 0x1, 0x14, rd, rs1, rs2),
+        BCLRI { rd, rs1, shamt } => r_type(0x13, 0x1, 0x24, rd, rs1, shamt),
+        BEXTI { rd, rs1, shamt } => r_type(0x13, 0x5, 0x24, rd, rs1, shamt),
+        BINVI { rd, rs1, shamt } => r_type(0x13, 0x1, 0x34, rd, rs1, shamt),
+        BSETI { rd, rs1, shamt } => r_type(0x13, 0x1, 0x14, rd, rs1, shamt),
+
+        ADDIW { rd, rs1, imm } => i_type(0x1B, 0x0, rd, rs1, imm),
+        SLLIW { rd, rs1, shamt } => r_type(0x1B, 0x1, 0x00, rd, rs1, shamt),
+        SRLIW { rd, rs1, shamt } => r_type(0x1B, 0x5, 0x00, rd, rs1, shamt),
+        SRAIW { rd, rs1, shamt } => r_type(0x1B, 0x5, 0x20, rd, rs1, shamt),
+        ADDW { rd, rs1, rs2 } => r_type(0x3B, 0x0, 0x00, rd, rs1, rs2),
+        SUBW { rd, rs1, rs2 } => r_type(0x3B, 0x0, 0x20, rd, rs1, rs2),
+        SLLW { rd, rs1, rs2 } => r_type(0x3B, 0x1, 0x00, rd, rs1, rs2),
+        SRLW { rd, rs1, rs2 } => r_type(0x3B, 0x5, 0x00, rd, rs1, rs2),
+        SRAW { rd, rs1, rs2 } => r_type(0x3B, 0x5, 0x20, rd, rs1, rs2),
+        MULW { rd, rs1, rs2 } => r_type(0x3B, 0x0, 0x01, rd, rs1, rs2),
+        DIVW { rd, rs1, rs2 } => r_type(0x3B, 0x4, 0x01, rd, rs1, rs2),
+        DIVUW { rd, rs1, rs2 } => r_type(0x3B, 0x5, 0x01, rd, rs1, rs2),
+        REMW { rd, rs1, rs2 } => r_type(0x3B, 0x6, 0x01, rd, rs1, rs2),
+        REMUW { rd, rs1, rs2 } => r_type(0x3B, 0x7, 0x01, rd, rs1, rs2),
+    }
+}
+
+fn reg(r: u8) -> u32 {
+    (r & 0x1F) as u32
+}
+
+fn r_type(opcode: u32, funct3: u32, funct7: u32, rd: u8, rs1: u8, rs2: u8) -> u32 {
+    (funct7 << 25) | (reg(rs2) << 20) | (reg(rs1) << 15) | (funct3 << 12) | (reg(rd) << 7) | opcode
+}
+fn shift_type(opcode: u32, funct3: u32, funct7: u32, rd: u8, rs1: u8, shamt: u8) -> u32 {
+    (funct7 << 25)
+        | ((shamt as u32) << 20)
+        | (reg(rs1) << 15)
+        | (funct3 << 12)
+        | (reg(rd) << 7)
+        | opcode
+}
+fn i_type(opcode: u32, funct3: u32, rd: u8, rs1: u8, imm: i32) -> u32 {
+    ((imm as u32 & 0xFFF) << 20) | (reg(rs1) << 15) | (funct3 << 12) | (reg(rd) << 7) | opcode
+}
+fn s_type(opcode: u32, funct3: u32, rs1: u8, rs2: u8, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 5) & 0x7F) << 25)
+        | (reg(rs2) << 20)
+        | (reg(rs1) << 15)
+        | (funct3 << 12)
+        | ((imm & 0x1F) << 7)
+        | opcode
+}
+fn b_type(funct3: u32, rs1: u8, rs2: u8, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 12) & 0x1) << 31)
+        | (((imm >> 5) & 0x3F) << 25)
+        | (reg(rs2) << 20)
+        | (reg(rs1) << 15)
+        | (funct3 << 12)
+        | (((imm >> 1) & 0xF) << 8)
+        | (((imm >> 11) & 0x1) << 7)
+        | 0x63
+}
+fn u_type(opcode: u32, rd: u8, imm: u32) -> u32 {
+    (imm & 0xFFFF_F000) | (reg(rd) << 7) | opcode
+}
+fn j_type(opcode: u32, rd: u8, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 20) & 0x1) << 31)
+        | (((imm >> 1) & 0x3FF) << 21)
+        | (((imm >> 11) & 0x1) << 20)
+        | (((imm >> 12) & 0xFF) << 12)
+        | (reg(rd) << 7)
+        | opcode
+}
+fn amo_type(funct5: u32, rd: u8, rs1: u8, rs2: u8, aq: bool, rl: bool) -> u32 {
+    let funct7 = (funct5 << 2) | ((aq as u32) << 1) | rl as u32;
+    r_type(0x2F, 0x2, funct7, rd, rs1, rs2)
+}
+fn csr_type(funct3: u32, rd: u8, rs1: u8, csr: u16) -> u32 {
+    (((csr & 0xFFF) as u32) << 20) | (reg(rs1) << 15) | (funct3 << 12) | (reg(rd) << 7) | 0x73
+}
@@ -0,0 +1,527 @@
+use super::*;
+use crate::decoder::{decode, decode_compressed, decode_rv64};
+
+type RType = fn(u8, u8, u8) -> Instruction;
+type IType = fn(u8, u8, i32) -> Instruction;
+type ShiftType = fn(u8, u8, u8) -> Instruction;
+type CsrType = fn(u8, u8, u16) -> Instruction;
+
+/// Registers used when another field is being swept.
+const SAMPLE_REGS: [u8; 8] = [0, 1, 2, 8, 9, 15, 16, 31];
+
+/// Checks `decode(encode(i)) == i`, and the same for the compressed form
+/// when `i` has one.
+fn round_trip(instr: Instruction) {
+    assert_eq!(decode(encode(&instr)), Ok(instr.clone()), "{instr:?}");
+    if let Some(half) = encode_compressed(&instr) {
+        assert_eq!(decode_compressed(half), Ok(instr.clone()), "{instr:?}");
+    }
+}
+
+fn round_trip_rv64(instr: Instruction) {
+    assert_eq!(decode_rv64(encode(&instr)), Ok(instr.clone()), "{instr:?}");
+}
+
+#[test]
+fn test_encode_known_words() {
+    use Instruction::*;
+    assert_eq!(
+        encode(&ADDI {
+            rd: 2,
+            rs1: 0,
+            imm: 5
+        }),
+        0x0050_0113
+    );
+    assert_eq!(
+        encode(&SW {
+            rs1: 2,
+            rs2: 10,
+            imm: -4
+        }),
+        0xFEA1_2E23
+    );
+    assert_eq!(
+        encode(&BNE {
+            rs1: 10,
+            rs2: 11,
+            imm: -8
+        }),
+        0xFEB5_1CE3
+    );
+    assert_eq!(encode(&JAL { rd: 1, imm: 2048 }), 0x0010_00EF);
+    assert_eq!(
+        encode(&CSRRS {
+            rd: 10,
+            rs1: 0,
+            csr: 0x342
+        }),
+        0x3420_2573
+    );
+    assert_eq!(
+        encode(&AMOADD_W {
+            rd: 10,
+            rs1: 12,
+            rs2: 11,
+            aq: true,
+            rl: true
+        }),
+        0x06B6_252F
+    );
+    assert_eq!(encode(&CLZ { rd: 10, rs1: 11 }), 0x6005_9513);
+    assert_eq!(encode(&WFI), 0x1050_0073);
+}
+
+#[test]
+fn test_encode_compressed_known_halves() {
+    use Instruction::*;
+    let cases = [
+        (
+            ADDI {
+                rd: 2,
+                rs1: 0,
+                imm: 5,
+            },
+            0x4115,
+        ),
+        (
+            ADDI {
+                rd: 8,
+                rs1: 2,
+                imm: 16,
+            },
+            0x0800,
+        ),
+        (
+            ADDI {
+                rd: 2,
+                rs1: 2,
+                imm: -64,
+            },
+            0x7139,
+        ),
+        (
+            LUI {
+                rd: 10,
+                imm: 0xFFFF_F000,
+            },
+            0x757D,
+        ),
+        (
+            LUI {
+                rd: 10,
+                imm: 0xFFFE_1000,
+            },
+            0x7505,
+        ),
+        (
+            LW {
+                rd: 10,
+                rs1: 2,
+                imm: 12,
+            },
+            0x4532,
+        ),
+        (JAL { rd: 0, imm: -2 }, 0xBFFD),
+        (
+            BNE {
+                rs1: 9,
+                rs2: 0,
+                imm: 20,
+            },
+            0xE891,
+        ),
+        (
+            SRAI {
+                rd: 15,
+                rs1: 15,
+                shamt: 3,
+            },
+            0x878D,
+        ),
+        (
+            AND {
+                rd: 8,
+                rs1: 8,
+                rs2: 15,
+            },
+            0x8C7D,
+        ),
+        (EBREAK, 0x9002),
+    ];
+    for (instr, half) in cases {
+        assert_eq!(encode_compressed(&instr), Some(half), "{instr:?}");
+    }
+}
+
+#[test]
+fn test_encode_compressed_rejects_uncompressible() {
+    use Instruction::*;
+    // immediate too wide, registers outside x8..x15, no RVC form at all
+    assert_eq!(
+        encode_compressed(&ADDI {
+            rd: 5,
+            rs1: 5,
+            imm: 32
+        }),
+        None
+    );
+    assert_eq!(
+        encode_compressed(&SUB {
+            rd: 5,
+            rs1: 5,
+            rs2: 8
+        }),
+        None
+    );
+    assert_eq!(
+        encode_compressed(&LW {
+            rd: 8,
+            rs1: 9,
+            imm: 2
+        }),
+        None
+    );
+    assert_eq!(encode_compressed(&ECALL), None);
+    assert_eq!(
+        encode_compressed(&MUL {
+            rd: 8,
+            rs1: 8,
+            rs2: 9
+        }),
+        None
+    );
+}
+
+#[test]
+fn test_round_trip_register_fields() {
+    use Instruction::*;
+    let r_types: [RType; 40] = [
+        |rd, rs1, rs2| ADD { rd, rs1, rs2 },
+        |rd, rs1, rs2| SUB { rd, rs1, rs2 },
+        |rd, rs1, rs2| SLL { rd, rs1, rs2 },
+        |rd, rs1, rs2| SLT { rd, rs1, rs2 },
+        |rd, rs1, rs2| SLTU { rd, rs1, rs2 },
+        |rd, rs1, rs2| XOR { rd, rs1, rs2 },
+        |rd, rs1, rs2| SRL { rd, rs1, rs2 },
+        |rd, rs1, rs2| SRA { rd, rs1, rs2 },
+        |rd, rs1, rs2| OR { rd, rs1, rs2 },
+        |rd, rs1, rs2| AND { rd, rs1, rs2 },
+        |rd, rs1, rs2| MUL { rd, rs1, rs2 },
+        |rd, rs1, rs2| MULH { rd, rs1, rs2 },
+        |rd, rs1, rs2| MULHSU { rd, rs1, rs2 },
+        |rd, rs1, rs2| MULHU { rd, rs1, rs2 },
+        |rd, rs1, rs2| DIV { rd, rs1, rs2 },
+        |rd, rs1, rs2| DIVU { rd, rs1, rs2 },
+        |rd, rs1, rs2| REM { rd, rs1, rs2 },
+        |rd, rs1, rs2| REMU { rd, rs1, rs2 },
+        |rd, rs1, rs2| SH1ADD { rd, rs1, rs2 },
+        |rd, rs1, rs2| SH2ADD { rd, rs1, rs2 },
+        |rd, rs1, rs2| SH3ADD { rd, rs1, rs2 },
+        |rd, rs1, rs2| ANDN { rd, rs1, rs2 },
+        |rd, rs1, rs2| ORN { rd, rs1, rs2 },
+        |rd, rs1, rs2| XNOR { rd, rs1, rs2 },
+        |rd, rs1, rs2| MIN { rd, rs1, rs2 },
+        |rd, rs1, rs2| MINU { rd, rs1, rs2 },
+        |rd, rs1, rs2| MAX { rd, rs1, rs2 },
+        |rd, rs1, rs2| MAXU { rd, rs1, rs2 },
+        |rd, rs1, rs2| ROL { rd, rs1, rs2 },
+        |rd, rs1, rs2| ROR { rd, rs1, rs2 },
+        |rd, rs1, rs2| BCLR { rd, rs1, rs2 },
+        |rd, rs1, rs2| BEXT { rd, rs1, rs2 },
+        |rd, rs1, rs2| BINV { rd, rs1, rs2 },
+        |rd, rs1, rs2| BSET { rd, rs1, rs2 },
+        |rd, rs1, _| CLZ { rd, rs1 },
+        |rd, rs1, _| CTZ { rd, rs1 },
+        |rd, rs1, _| CPOP { rd, rs1 },
+        |rd, rs1, _| ZEXT_H { rd, rs1 },
+        |rd, rs1, _| ORC_B { rd, rs1 },
+        |_, rs1, rs2| SFENCE_VMA { rs1, rs2 },
+    ];
+    for ctor in r_types {
+        for rd in 0..32 {
+            for rs1 in 0..32 {
+                for rs2 in 0..32 {
+                    round_trip(ctor(rd, rs1, rs2));
+                }
+            }
+        }
+    }
+    for rd in 0..32 {
+        for rs1 in 0..32 {
+            round_trip(SEXT_B { rd, rs1 });
+            round_trip(SEXT_H { rd, rs1 });
+            round_trip(REV8 { rd, rs1 });
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_atomics() {
+    use Instruction::*;
+    type AmoType = fn(u8, u8, u8, bool, bool) -> Instruction;
+    let amos: [AmoType; 10] = [
+        |rd, rs1, rs2, aq, rl| SC_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOSWAP_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOADD_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOXOR_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOAND_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOOR_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOMIN_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOMAX_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOMINU_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+        |rd, rs1, rs2, aq, rl| AMOMAXU_W {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+        },
+    ];
+    for (aq, rl) in [(false, false), (false, true), (true, false), (true, true)] {
+        for rd in 0..32 {
+            for rs1 in 0..32 {
+                round_trip(LR_W { rd, rs1, aq, rl });
+                for rs2 in 0..32 {
+                    for ctor in amos {
+                        round_trip(ctor(rd, rs1, rs2, aq, rl));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_immediates() {
+    use Instruction::*;
+    let i_types: [IType; 12] = [
+        |rd, rs1, imm| JALR { rd, rs1, imm },
+        |rd, rs1, imm| LB { rd, rs1, imm },
+        |rd, rs1, imm| LH { rd, rs1, imm },
+        |rd, rs1, imm| LW { rd, rs1, imm },
+        |rd, rs1, imm| LBU { rd, rs1, imm },
+        |rd, rs1, imm| LHU { rd, rs1, imm },
+        |rd, rs1, imm| ADDI { rd, rs1, imm },
+        |rd, rs1, imm| SLTI { rd, rs1, imm },
+        |rd, rs1, imm| SLTIU { rd, rs1, imm },
+        |rd, rs1, imm| XORI { rd, rs1, imm },
+        |rd, rs1, imm| ORI { rd, rs1, imm },
+        |rd, rs1, imm| ANDI { rd, rs1, imm },
+    ];
+    let s_types: [IType; 9] = [
+        |rs1, rs2, imm| SB { rs1, rs2, imm },
+        |rs1, rs2, imm| SH { rs1, rs2, imm },
+        |rs1, rs2, imm| SW { rs1, rs2, imm },
+        |rs1, rs2, imm| BEQ { rs1, rs2, imm },
+        |rs1, rs2, imm| BNE { rs1, rs2, imm },
+        |rs1, rs2, imm| BLT { rs1, rs2, imm },
+        |rs1, rs2, imm| BGE { rs1, rs2, imm },
+        |rs1, rs2, imm| BLTU { rs1, rs2, imm },
+        |rs1, rs2, imm| BGEU { rs1, rs2, imm },
+    ];
+    for a in SAMPLE_REGS {
+        for b in SAMPLE_REGS {
+            for imm in -2048..2048 {
+                for ctor in i_types {
+                    round_trip(ctor(a, b, imm));
+                }
+                for ctor in &s_types[..3] {
+                    round_trip(ctor(a, b, imm));
+                }
+                for ctor in &s_types[3..] {
+                    round_trip(ctor(a, b, imm * 2));
+                }
+            }
+        }
+    }
+    for rd in [0, 1, 31] {
+        for upper in 0..1 << 20 {
+            round_trip(LUI {
+                rd,
+                imm: upper << 12,
+            });
+            round_trip(AUIPC {
+                rd,
+                imm: upper << 12,
+            });
+            let imm = ((upper << 12) as i32) >> 11;
+            round_trip(JAL { rd, imm });
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_shifts_and_csrs() {
+    use Instruction::*;
+    let shifts: [ShiftType; 8] = [
+        |rd, rs1, shamt| SLLI { rd, rs1, shamt },
+        |rd, rs1, shamt| SRLI { rd, rs1, shamt },
+        |rd, rs1, shamt| SRAI { rd, rs1, shamt },
+        |rd, rs1, shamt| RORI { rd, rs1, shamt },
+        |rd, rs1, shamt| BCLRI { rd, rs1, shamt },
+        |rd, rs1, shamt| BEXTI { rd, rs1, shamt },
+        |rd, rs1, shamt| BINVI { rd, rs1, shamt },
+        |rd, rs1, shamt| BSETI { rd, rs1, shamt },
+    ];
+    let csrs: [CsrType; 6] = [
+        |rd, rs1, csr| CSRRW { rd, rs1, csr },
+        |rd, rs1, csr| CSRRS { rd, rs1, csr },
+        |rd, rs1, csr| CSRRC { rd, rs1, csr },
+        |rd, uimm, csr| CSRRWI { rd, uimm, csr },
+        |rd, uimm, csr| CSRRSI { rd, uimm, csr },
+        |rd, uimm, csr| CSRRCI { rd, uimm, csr },
+    ];
+    for rd in 0..32 {
+        for rs1 in 0..32 {
+            for shamt in 0..32 {
+                for ctor in shifts {
+                    round_trip(ctor(rd, rs1, shamt));
+                }
+            }
+        }
+    }
+    for rd in SAMPLE_REGS {
+        for rs1 in SAMPLE_REGS {
+            for csr in 0..0x1000 {
+                for ctor in csrs {
+                    round_trip(ctor(rd, rs1, csr));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_system() {
+    use Instruction::*;
+    for instr in [FENCE_I, ECALL, EBREAK, MRET, WFI] {
+        round_trip(instr);
+    }
+    for fm in 0..16 {
+        for pred in 0..16 {
+            for succ in 0..16 {
+                round_trip(FENCE { fm, pred, succ });
+            }
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_rv64_only() {
+    use Instruction::*;
+    let r_types: [RType; 10] = [
+        |rd, rs1, rs2| ADDW { rd, rs1, rs2 },
+        |rd, rs1, rs2| SUBW { rd, rs1, rs2 },
+        |rd, rs1, rs2| SLLW { rd, rs1, rs2 },
+        |rd, rs1, rs2| SRLW { rd, rs1, rs2 },
+        |rd, rs1, rs2| SRAW { rd, rs1, rs2 },
+        |rd, rs1, rs2| MULW { rd, rs1, rs2 },
+        |rd, rs1, rs2| DIVW { rd, rs1, rs2 },
+        |rd, rs1, rs2| DIVUW { rd, rs1, rs2 },
+        |rd, rs1, rs2| REMW { rd, rs1, rs2 },
+        |rd, rs1, rs2| REMUW { rd, rs1, rs2 },
+    ];
+    for rd in 0..32 {
+        for rs1 in 0..32 {
+            for rs2 in 0..32 {
+                for ctor in r_types {
+                    round_trip_rv64(ctor(rd, rs1, rs2));
+                }
+            }
+            for shamt in 0..64 {
+                round_trip_rv64(SLLI { rd, rs1, shamt });
+                round_trip_rv64(SRLI { rd, rs1, shamt });
+                round_trip_rv64(SRAI { rd, rs1, shamt });
+            }
+            for shamt in 0..32 {
+                round_trip_rv64(SLLIW { rd, rs1, shamt });
+                round_trip_rv64(SRLIW { rd, rs1, shamt });
+                round_trip_rv64(SRAIW { rd, rs1, shamt });
+            }
+        }
+    }
+    for a in SAMPLE_REGS {
+        for b in SAMPLE_REGS {
+            for imm in -2048..2048 {
+                round_trip_rv64(LD { rd: a, rs1: b, imm });
+                round_trip_rv64(LWU { rd: a, rs1: b, imm });
+                round_trip_rv64(SD {
+                    rs1: a,
+                    rs2: b,
+                    imm,
+                });
+                round_trip_rv64(ADDIW { rd: a, rs1: b, imm });
+            }
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_every_compressed_half() {
+    for half in 0..=u16::MAX {
+        if half & 0x3 == 0x3 {
+            continue;
+        }
+        let Ok(instr) = decode_compressed(half) else {
+            continue;
+        };
+        let encoded = encode_compressed(&instr)
+            .unwrap_or_else(|| panic!("{half:#06x} ({instr:?}) has no compressed encoding"));
+        assert_eq!(decode_compressed(encoded), Ok(instr));
+    }
+}
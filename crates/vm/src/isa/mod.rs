@@ -0,0 +1,69 @@
+/// Which optional extensions the VM accepts on top of the RV32I base.
+///
+/// Instructions from a disabled extension fail to decode with
+/// [`DecodeError::ExtensionDisabled`](crate::decoder::errors::DecodeError::ExtensionDisabled),
+/// so the VM can match the exact target a guest was compiled for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IsaConfig {
+    /// RV32E: only x0-x15 exist, and syscall numbers are passed in t0.
+    pub e: bool,
+    /// M: integer multiply and divide.
+    pub m: bool,
+    /// A: `lr.w`, `sc.w` and the AMOs.
+    pub a: bool,
+    /// C: 16-bit compressed encodings.
+    pub c: bool,
+    /// Zicsr: the `csrr*` instructions.
+    pub zicsr: bool,
+    /// Zba: address generation (`sh1add`, `sh2add`, `sh3add`).
+    pub zba: bool,
+    /// Zbb: basic bit manipulation (`andn`, `clz`, `rori`, `rev8`, ...).
+    pub zbb: bool,
+    /// Zbs: single-bit instructions (`bset`, `bclr`, `binv`, `bext`).
+    pub zbs: bool,
+}
+
+impl IsaConfig {
+    /// The bare RV32I base, with every extension turned off.
+    pub fn rv32i() -> Self {
+        IsaConfig {
+            e: false,
+            m: false,
+            a: false,
+            c: false,
+            zicsr: false,
+            zba: false,
+            zbb: false,
+            zbs: false,
+        }
+    }
+
+    /// Plain rv32im, without any of the other extensions.
+    pub fn rv32im() -> Self {
+        IsaConfig {
+            m: true,
+            ..Self::rv32i()
+        }
+    }
+
+    /// Number of general-purpose registers, counting x0.
+    pub fn num_regs(&self) -> usize {
+        if self.e { 16 } else { 32 }
+    }
+}
+
+impl Default for IsaConfig {
+    /// Everything the VM implements.
+    fn default() -> Self {
+        IsaConfig {
+            e: false,
+            m: true,
+            a: true,
+            c: true,
+            zicsr: true,
+            zba: true,
+            zbb: true,
+            zbs: true,
+        }
+    }
+}
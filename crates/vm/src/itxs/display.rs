@@ -0,0 +1,522 @@
+use std::fmt;
+
+use crate::constants::{
+    CSR_CYCLE, CSR_CYCLEH, CSR_INSTRET, CSR_INSTRETH, CSR_MCAUSE, CSR_MCYCLE, CSR_MCYCLEH,
+    CSR_MEPC, CSR_MIE, CSR_MINSTRET, CSR_MINSTRETH, CSR_MIP, CSR_MSCRATCH, CSR_MSTATUS, CSR_MTVAL,
+    CSR_MTVEC, CSR_PMPADDR_LAST, CSR_PMPADDR0, CSR_PMPCFG_LAST, CSR_PMPCFG0, CSR_SATP, CSR_TIME,
+    CSR_TIMEH,
+};
+
+use super::Instruction;
+
+const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// The ABI name of register `reg`, such as `sp` for x2.
+pub fn abi_name(reg: u8) -> &'static str {
+    ABI_NAMES.get(reg as usize).copied().unwrap_or("x?")
+}
+
+struct Reg(u8);
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(abi_name(self.0))
+    }
+}
+
+/// A branch or jump offset, shown as the absolute target address once the
+/// instruction's own address is known.
+struct Target {
+    imm: i32,
+    pc: Option<u32>,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pc {
+            Some(pc) => write!(f, "{:#x}", pc.wrapping_add(self.imm as u32)),
+            None => write!(f, "{}", self.imm),
+        }
+    }
+}
+
+struct Csr(u16);
+
+impl fmt::Display for Csr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            CSR_CYCLE => "cycle",
+            CSR_TIME => "time",
+            CSR_INSTRET => "instret",
+            CSR_CYCLEH => "cycleh",
+            CSR_TIMEH => "timeh",
+            CSR_INSTRETH => "instreth",
+            CSR_MCYCLE => "mcycle",
+            CSR_MINSTRET => "minstret",
+            CSR_MCYCLEH => "mcycleh",
+            CSR_MINSTRETH => "minstreth",
+            CSR_SATP => "satp",
+            CSR_MSTATUS => "mstatus",
+            CSR_MIE => "mie",
+            CSR_MTVEC => "mtvec",
+            CSR_MSCRATCH => "mscratch",
+            CSR_MEPC => "mepc",
+            CSR_MCAUSE => "mcause",
+            CSR_MTVAL => "mtval",
+            CSR_MIP => "mip",
+            csr @ CSR_PMPCFG0..=CSR_PMPCFG_LAST => {
+                return write!(f, "pmpcfg{}", csr - CSR_PMPCFG0);
+            }
+            csr @ CSR_PMPADDR0..=CSR_PMPADDR_LAST => {
+                return write!(f, "pmpaddr{}", csr - CSR_PMPADDR0);
+            }
+            csr => return write!(f, "{csr:#x}"),
+        };
+        f.write_str(name)
+    }
+}
+
+/// The `iorw` set of a FENCE predecessor or successor field.
+struct FenceSet(u8);
+
+impl fmt::Display for FenceSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return f.write_str("0");
+        }
+        for (bit, name) in [(8, 'i'), (4, 'o'), (2, 'r'), (1, 'w')] {
+            if self.0 & bit != 0 {
+                write!(f, "{name}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Instruction {
+    /// The assembly mnemonic, such as `addi` or `amoadd.w`.
+    pub fn mnemonic(&self) -> &'static str {
+        use Instruction::*;
+        match self {
+            LUI { .. } => "lui",
+            AUIPC { .. } => "auipc",
+            JAL { .. } => "jal",
+            JALR { .. } => "jalr",
+            LB { .. } => "lb",
+            LH { .. } => "lh",
+            LW { .. } => "lw",
+            LBU { .. } => "lbu",
+            LHU { .. } => "lhu",
+            ADDI { .. } => "addi",
+            SLTI { .. } => "slti",
+            SLTIU { .. } => "sltiu",
+            XORI { .. } => "xori",
+            ORI { .. } => "ori",
+            ANDI { .. } => "andi",
+            SLLI { .. } => "slli",
+            SRLI { .. } => "srli",
+            SRAI { .. } => "srai",
+            SB { .. } => "sb",
+            SH { .. } => "sh",
+            SW { .. } => "sw",
+            BEQ { .. } => "beq",
+            BNE { .. } => "bne",
+            BLT { .. } => "blt",
+            BGE { .. } => "bge",
+            BLTU { .. } => "bltu",
+            BGEU { .. } => "bgeu",
+            ADD { .. } => "add",
+            SUB { .. } => "sub",
+            SLL { .. } => "sll",
+            SLT { .. } => "slt",
+            SLTU { .. } => "sltu",
+            XOR { .. } => "xor",
+            SRL { .. } => "srl",
+            SRA { .. } => "sra",
+            OR { .. } => "or",
+            AND { .. } => "and",
+            FENCE { .. } => "fence",
+            FENCE_I => "fence.i",
+            ECALL => "ecall",
+            EBREAK => "ebreak",
+            MRET => "mret",
+            WFI => "wfi",
+            SFENCE_VMA { .. } => "sfence.vma",
+            LR_W { .. } => "lr.w",
+            SC_W { .. } => "sc.w",
+            AMOSWAP_W { .. } => "amoswap.w",
+            AMOADD_W { .. } => "amoadd.w",
+            AMOXOR_W { .. } => "amoxor.w",
+            AMOAND_W { .. } => "amoand.w",
+            AMOOR_W { .. } => "amoor.w",
+            AMOMIN_W { .. } => "amomin.w",
+            AMOMAX_W { .. } => "amomax.w",
+            AMOMINU_W { .. } => "amominu.w",
+            AMOMAXU_W { .. } => "amomaxu.w",
+            CSRRW { .. } => "csrrw",
+            CSRRS { .. } => "csrrs",
+            CSRRC { .. } => "csrrc",
+            CSRRWI { .. } => "csrrwi",
+            CSRRSI { .. } => "csrrsi",
+            CSRRCI { .. } => "csrrci",
+            MUL { .. } => "mul",
+            MULH { .. } => "mulh",
+            MULHSU { .. } => "mulhsu",
+            MULHU { .. } => "mulhu",
+            DIV { .. } => "div",
+            DIVU { .. } => "divu",
+            REM { .. } => "rem",
+            REMU { .. } => "remu",
+            SH1ADD { .. } => "sh1add",
+            SH2ADD { .. } => "sh2add",
+            SH3ADD { .. } => "sh3add",
+            ANDN { .. } => "andn",
+            ORN { .. } => "orn",
+            XNOR { .. } => "xnor",
+            MIN { .. } => "min",
+            MINU { .. } => "minu",
+            MAX { .. } => "max",
+            MAXU { .. } => "maxu",
+            ROL { .. } => "rol",
+            ROR { .. } => "ror",
+            BCLR { .. } => "bclr",
+            BEXT { .. } => "bext",
+            BINV { .. } => "binv",
+            BSET { .. } => "bset",
+            CLZ { .. } => "clz",
+            CTZ { .. } => "ctz",
+            CPOP { .. } => "cpop",
+            SEXT_B { .. } => "sext.b",
+            SEXT_H { .. } => "sext.h",
+            ZEXT_H { .. } => "zext.h",
+            ORC_B { .. } => "orc.b",
+            REV8 { .. } => "rev8",
+            RORI { .. } => "rori",
+            BCLRI { .. } => "bclri",
+            BEXTI { .. } => "bexti",
+            BINVI { .. } => "binvi",
+            BSETI { .. } => "bseti",
+            LWU { .. } => "lwu",
+            LD { .. } => "ld",
+            SD { .. } => "sd",
+            ADDIW { .. } => "addiw",
+            SLLIW { .. } => "slliw",
+            SRLIW { .. } => "srliw",
+            SRAIW { .. } => "sraiw",
+            ADDW { .. } => "addw",
+            SUBW { .. } => "subw",
+            SLLW { .. } => "sllw",
+            SRLW { .. } => "srlw",
+            SRAW { .. } => "sraw",
+            MULW { .. } => "mulw",
+            DIVW { .. } => "divw",
+            DIVUW { .. } => "divuw",
+            REMW { .. } => "remw",
+            REMUW { .. } => "remuw",
+        }
+    }
+
+    /// Writes the pseudo-instruction this instruction is an alias of, if
+    /// the assembler has one for it.
+    fn fmt_pseudo(&self, f: &mut fmt::Formatter<'_>, pc: Option<u32>) -> Option<fmt::Result> {
+        use Instruction::*;
+        let target = |imm| Target { imm, pc };
+        let res = match *self {
+            ADDI {
+                rd: 0,
+                rs1: 0,
+                imm: 0,
+            } => f.write_str("nop"),
+            ADDI { rd, rs1: 0, imm } => write!(f, "li {}, {imm}", Reg(rd)),
+            ADDI { rd, rs1, imm: 0 } => write!(f, "mv {}, {}", Reg(rd), Reg(rs1)),
+            XORI { rd, rs1, imm: -1 } => write!(f, "not {}, {}", Reg(rd), Reg(rs1)),
+            SLTIU { rd, rs1, imm: 1 } => write!(f, "seqz {}, {}", Reg(rd), Reg(rs1)),
+            ADDIW { rd, rs1, imm: 0 } => write!(f, "sext.w {}, {}", Reg(rd), Reg(rs1)),
+            SUB { rd, rs1: 0, rs2 } => write!(f, "neg {}, {}", Reg(rd), Reg(rs2)),
+            SLTU { rd, rs1: 0, rs2 } => write!(f, "snez {}, {}", Reg(rd), Reg(rs2)),
+            SLT { rd, rs1, rs2: 0 } => write!(f, "sltz {}, {}", Reg(rd), Reg(rs1)),
+            SLT { rd, rs1: 0, rs2 } => write!(f, "sgtz {}, {}", Reg(rd), Reg(rs2)),
+            BEQ { rs1, rs2: 0, imm } => write!(f, "beqz {}, {}", Reg(rs1), target(imm)),
+            BNE { rs1, rs2: 0, imm } => write!(f, "bnez {}, {}", Reg(rs1), target(imm)),
+            BGE { rs1: 0, rs2, imm } => write!(f, "blez {}, {}", Reg(rs2), target(imm)),
+            BGE { rs1, rs2: 0, imm } => write!(f, "bgez {}, {}", Reg(rs1), target(imm)),
+            BLT { rs1, rs2: 0, imm } => write!(f, "bltz {}, {}", Reg(rs1), target(imm)),
+            BLT { rs1: 0, rs2, imm } => write!(f, "bgtz {}, {}", Reg(rs2), target(imm)),
+            JAL { rd: 0, imm } => write!(f, "j {}", target(imm)),
+            JAL { rd: 1, imm } => write!(f, "jal {}", target(imm)),
+            JALR {
+                rd: 0,
+                rs1: 1,
+                imm: 0,
+            } => f.write_str("ret"),
+            JALR { rd: 0, rs1, imm: 0 } => write!(f, "jr {}", Reg(rs1)),
+            JALR { rd: 1, rs1, imm: 0 } => write!(f, "jalr {}", Reg(rs1)),
+            CSRRS { rd, rs1: 0, csr } => write!(f, "csrr {}, {}", Reg(rd), Csr(csr)),
+            CSRRW { rd: 0, rs1, csr } => write!(f, "csrw {}, {}", Csr(csr), Reg(rs1)),
+            CSRRS { rd: 0, rs1, csr } => write!(f, "csrs {}, {}", Csr(csr), Reg(rs1)),
+            CSRRC { rd: 0, rs1, csr } => write!(f, "csrc {}, {}", Csr(csr), Reg(rs1)),
+            CSRRWI { rd: 0, uimm, csr } => write!(f, "csrwi {}, {uimm}", Csr(csr)),
+            CSRRSI { rd: 0, uimm, csr } => write!(f, "csrsi {}, {uimm}", Csr(csr)),
+            CSRRCI { rd: 0, uimm, csr } => write!(f, "csrci {}, {uimm}", Csr(csr)),
+            FENCE {
+                fm: 0,
+                pred: 0xF,
+                succ: 0xF,
+            } => f.write_str("fence"),
+            FENCE {
+                fm: 0x8,
+                pred: 0x3,
+                succ: 0x3,
+            } => f.write_str("fence.tso"),
+            _ => return None,
+        };
+        Some(res)
+    }
+
+    /// Displays the instruction as it reads at address `pc`: like its
+    /// [`Display`](fmt::Display) form, but with branch and jump targets
+    /// resolved to absolute addresses, e.g. `j 0x1018`.
+    pub fn at(&self, pc: u32) -> AtPc<'_> {
+        AtPc { instr: self, pc }
+    }
+
+    fn fmt_at(&self, f: &mut fmt::Formatter<'_>, pc: Option<u32>) -> fmt::Result {
+        use Instruction::*;
+        if !f.alternate()
+            && let Some(res) = self.fmt_pseudo(f, pc)
+        {
+            return res;
+        }
+        let m = self.mnemonic();
+        let target = |imm| Target { imm, pc };
+        match *self {
+            LUI { rd, imm } | AUIPC { rd, imm } => write!(f, "{m} {}, {:#x}", Reg(rd), imm >> 12),
+            JAL { rd, imm } => write!(f, "{m} {}, {}", Reg(rd), target(imm)),
+            JALR { rd, rs1, imm }
+            | LB { rd, rs1, imm }
+            | LH { rd, rs1, imm }
+            | LW { rd, rs1, imm }
+            | LBU { rd, rs1, imm }
+            | LHU { rd, rs1, imm }
+            | LWU { rd, rs1, imm }
+            | LD { rd, rs1, imm } => write!(f, "{m} {}, {imm}({})", Reg(rd), Reg(rs1)),
+            SB { rs1, rs2, imm }
+            | SH { rs1, rs2, imm }
+            | SW { rs1, rs2, imm }
+            | SD { rs1, rs2, imm } => {
+                write!(f, "{m} {}, {imm}({})", Reg(rs2), Reg(rs1))
+            }
+            BEQ { rs1, rs2, imm }
+            | BNE { rs1, rs2, imm }
+            | BLT { rs1, rs2, imm }
+            | BGE { rs1, rs2, imm }
+            | BLTU { rs1, rs2, imm }
+            | BGEU { rs1, rs2, imm } => {
+                write!(f, "{m} {}, {}, {}", Reg(rs1), Reg(rs2), target(imm))
+            }
+            ADDI { rd, rs1, imm }
+            | SLTI { rd, rs1, imm }
+            | SLTIU { rd, rs1, imm }
+            | XORI { rd, rs1, imm }
+            | ORI { rd, rs1, imm }
+            | ANDI { rd, rs1, imm }
+            | ADDIW { rd, rs1, imm } => write!(f, "{m} {}, {}, {imm}", Reg(rd), Reg(rs1)),
+            SLLI { rd, rs1, shamt }
+            | SRLI { rd, rs1, shamt }
+            | SRAI { rd, rs1, shamt }
+            | RORI { rd, rs1, shamt }
+            | BCLRI { rd, rs1, shamt }
+            | BEXTI { rd, rs1, shamt }
+            | BINVI { rd, rs1, shamt }
+            | BSETI { rd, rs1, shamt }
+            | SLLIW { rd, rs1, shamt }
+            | SRLIW { rd, rs1, shamt }
+            | SRAIW { rd, rs1, shamt } => write!(f, "{m} {}, {}, {shamt}", Reg(rd), Reg(rs1)),
+            CLZ { rd, rs1 }
+            | CTZ { rd, rs1 }
+            | CPOP { rd, rs1 }
+            | SEXT_B { rd, rs1 }
+            | SEXT_H { rd, rs1 }
+            | ZEXT_H { rd, rs1 }
+            | ORC_B { rd, rs1 }
+            | REV8 { rd, rs1 } => write!(f, "{m} {}, {}", Reg(rd), Reg(rs1)),
+            FENCE { pred, succ, .. } => {
+                write!(f, "{m} {}, {}", FenceSet(pred), FenceSet(succ))
+            }
+            FENCE_I | ECALL | EBREAK | MRET | WFI => f.write_str(m),
+            SFENCE_VMA { rs1: 0, rs2: 0 } => f.write_str(m),
+            SFENCE_VMA { rs1, rs2: 0 } => write!(f, "{m} {}", Reg(rs1)),
+            SFENCE_VMA { rs1, rs2 } => write!(f, "{m} {}, {}", Reg(rs1), Reg(rs2)),
+            LR_W { rd, rs1, aq, rl } => {
+                write!(f, "{m}{} {}, ({})", ordering(aq, rl), Reg(rd), Reg(rs1))
+            }
+            SC_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOSWAP_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOADD_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOXOR_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOAND_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOOR_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOMIN_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOMAX_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOMINU_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            }
+            | AMOMAXU_W {
+                rd,
+                rs1,
+                rs2,
+                aq,
+                rl,
+            } => write!(
+                f,
+                "{m}{} {}, {}, ({})",
+                ordering(aq, rl),
+                Reg(rd),
+                Reg(rs2),
+                Reg(rs1)
+            ),
+            CSRRW { rd, rs1, csr } | CSRRS { rd, rs1, csr } | CSRRC { rd, rs1, csr } => {
+                write!(f, "{m} {}, {}, {}", Reg(rd), Csr(csr), Reg(rs1))
+            }
+            CSRRWI { rd, uimm, csr } | CSRRSI { rd, uimm, csr } | CSRRCI { rd, uimm, csr } => {
+                write!(f, "{m} {}, {}, {uimm}", Reg(rd), Csr(csr))
+            }
+            ADD { rd, rs1, rs2 }
+            | SUB { rd, rs1, rs2 }
+            | SLL { rd, rs1, rs2 }
+            | SLT { rd, rs1, rs2 }
+            | SLTU { rd, rs1, rs2 }
+            | XOR { rd, rs1, rs2 }
+            | SRL { rd, rs1, rs2 }
+            | SRA { rd, rs1, rs2 }
+            | OR { rd, rs1, rs2 }
+            | AND { rd, rs1, rs2 }
+            | MUL { rd, rs1, rs2 }
+            | MULH { rd, rs1, rs2 }
+            | MULHSU { rd, rs1, rs2 }
+            | MULHU { rd, rs1, rs2 }
+            | DIV { rd, rs1, rs2 }
+            | DIVU { rd, rs1, rs2 }
+            | REM { rd, rs1, rs2 }
+            | REMU { rd, rs1, rs2 }
+            | SH1ADD { rd, rs1, rs2 }
+            | SH2ADD { rd, rs1, rs2 }
+            | SH3ADD { rd, rs1, rs2 }
+            | ANDN { rd, rs1, rs2 }
+            | ORN { rd, rs1, rs2 }
+            | XNOR { rd, rs1, rs2 }
+            | MIN { rd, rs1, rs2 }
+            | MINU { rd, rs1, rs2 }
+            | MAX { rd, rs1, rs2 }
+            | MAXU { rd, rs1, rs2 }
+            | ROL { rd, rs1, rs2 }
+            | ROR { rd, rs1, rs2 }
+            | BCLR { rd, rs1, rs2 }
+            | BEXT { rd, rs1, rs2 }
+            | BINV { rd, rs1, rs2 }
+            | BSET { rd, rs1, rs2 }
+            | ADDW { rd, rs1, rs2 }
+            | SUBW { rd, rs1, rs2 }
+            | SLLW { rd, rs1, rs2 }
+            | SRLW { rd, rs1, rs2 }
+            | SRAW { rd, rs1, rs2 }
+            | MULW { rd, rs1, rs2 }
+            | DIVW { rd, rs1, rs2 }
+            | DIVUW { rd, rs1, rs2 }
+            | REMW { rd, rs1, rs2 }
+            | REMUW { rd, rs1, rs2 } => {
+                write!(f, "{m} {}, {}, {}", Reg(rd), Reg(rs1), Reg(rs2))
+            }
+        }
+    }
+}
+
+/// An [`Instruction`] displayed at a known address; see [`Instruction::at`].
+pub struct AtPc<'a> {
+    instr: &'a Instruction,
+    pc: u32,
+}
+
+/// Formats the instruction in standard assembly syntax with ABI register
+/// names, e.g. `addi sp, sp, -16` or `lw a0, 8(sp)`. Aliases such as `li`,
+/// `mv`, `ret` and `j` are used where they apply, unless the alternate
+/// flag (`{:#}`) asks for the underlying instruction. Branch and jump
+/// targets are printed as offsets from the instruction's own address;
+/// [`Instruction::at`] resolves them.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_at(f, None)
+    }
+}
+
+impl fmt::Display for AtPc<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.instr.fmt_at(f, Some(self.pc))
+    }
+}
+
+/// The `.aq`/`.rl`/`.aqrl` suffix of an atomic.
+fn ordering(aq: bool, rl: bool) -> &'static str {
+    match (aq, rl) {
+        (false, false) => "",
+        (true, false) => ".aq",
+        (false, true) => ".rl",
+        (true, true) => ".aqrl",
+    }
+}
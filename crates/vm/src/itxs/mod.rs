@@ -1,3 +1,9 @@
+mod display;
+#[cfg(test)]
+mod tests;
+
+pub use display::{AtPc, abi_name};
+
 #[derive(Debug, PartialEq, Clone)]
 #[allow(non_camel_case_types)]
 pub enum Instruction {
@@ -18,45 +24,311 @@ pub enum Instruction {
         rs1: u8,
         imm: i32,
     },
-    LOAD {
+    LB {
         rd: u8,
         rs1: u8,
         imm: i32,
     },
-    OP_IMM {
+    LH {
         rd: u8,
         rs1: u8,
         imm: i32,
     },
-    SHIFT_IMM {
+    LW {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    LBU {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    LHU {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    ADDI {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    SLTI {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    SLTIU {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    XORI {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    ORI {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    ANDI {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    SLLI {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    SRLI {
         rd: u8,
         rs1: u8,
         shamt: u8,
-        is_srai: bool,
     },
-    STORE {
+    SRAI {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    SB {
+        rs1: u8,
+        rs2: u8,
+        imm: i32,
+    },
+    SH {
+        rs1: u8,
+        rs2: u8,
+        imm: i32,
+    },
+    SW {
+        rs1: u8,
+        rs2: u8,
+        imm: i32,
+    },
+    BEQ {
+        rs1: u8,
+        rs2: u8,
+        imm: i32,
+    },
+    BNE {
+        rs1: u8,
+        rs2: u8,
+        imm: i32,
+    },
+    BLT {
+        rs1: u8,
+        rs2: u8,
+        imm: i32,
+    },
+    BGE {
         rs1: u8,
         rs2: u8,
         imm: i32,
     },
-    BRANCH {
+    BLTU {
         rs1: u8,
         rs2: u8,
         imm: i32,
     },
-    OP {
+    BGEU {
+        rs1: u8,
+        rs2: u8,
+        imm: i32,
+    },
+    ADD {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SUB {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SLL {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SLT {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SLTU {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    XOR {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SRL {
         rd: u8,
         rs1: u8,
         rs2: u8,
-        funct7: u8,
     },
+    SRA {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    OR {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    AND {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    FENCE {
+        fm: u8,
+        pred: u8,
+        succ: u8,
+    },
+    FENCE_I,
     ECALL,
     EBREAK,
+    MRET,
+    WFI,
+    SFENCE_VMA {
+        rs1: u8,
+        rs2: u8,
+    },
+    LR_W {
+        rd: u8,
+        rs1: u8,
+        aq: bool,
+        rl: bool,
+    },
+    SC_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOSWAP_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOADD_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOXOR_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOAND_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOOR_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOMIN_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOMAX_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOMINU_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    AMOMAXU_W {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+        aq: bool,
+        rl: bool,
+    },
+    CSRRW {
+        rd: u8,
+        rs1: u8,
+        csr: u16,
+    },
+    CSRRS {
+        rd: u8,
+        rs1: u8,
+        csr: u16,
+    },
+    CSRRC {
+        rd: u8,
+        rs1: u8,
+        csr: u16,
+    },
+    CSRRWI {
+        rd: u8,
+        uimm: u8,
+        csr: u16,
+    },
+    CSRRSI {
+        rd: u8,
+        uimm: u8,
+        csr: u16,
+    },
+    CSRRCI {
+        rd: u8,
+        uimm: u8,
+        csr: u16,
+    },
     MUL {
         rd: u8,
         rs1: u8,
         rs2: u8,
     },
+    MULH {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    MULHSU {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    MULHU {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
     DIV {
         rd: u8,
         rs1: u8,
@@ -77,4 +349,346 @@ pub enum Instruction {
         rs1: u8,
         rs2: u8,
     },
+    SH1ADD {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SH2ADD {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SH3ADD {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    ANDN {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    ORN {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    XNOR {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    MIN {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    MINU {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    MAX {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    MAXU {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    ROL {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    ROR {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    BCLR {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    BEXT {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    BINV {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    BSET {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    CLZ {
+        rd: u8,
+        rs1: u8,
+    },
+    CTZ {
+        rd: u8,
+        rs1: u8,
+    },
+    CPOP {
+        rd: u8,
+        rs1: u8,
+    },
+    SEXT_B {
+        rd: u8,
+        rs1: u8,
+    },
+    SEXT_H {
+        rd: u8,
+        rs1: u8,
+    },
+    ZEXT_H {
+        rd: u8,
+        rs1: u8,
+    },
+    ORC_B {
+        rd: u8,
+        rs1: u8,
+    },
+    REV8 {
+        rd: u8,
+        rs1: u8,
+    },
+    RORI {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    BCLRI {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    BEXTI {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    BINVI {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    BSETI {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    LWU {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    LD {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    SD {
+        rs1: u8,
+        rs2: u8,
+        imm: i32,
+    },
+    ADDIW {
+        rd: u8,
+        rs1: u8,
+        imm: i32,
+    },
+    SLLIW {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    SRLIW {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    SRAIW {
+        rd: u8,
+        rs1: u8,
+        shamt: u8,
+    },
+    ADDW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SUBW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SLLW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SRLW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    SRAW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    MULW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    DIVW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    DIVUW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    REMW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+    REMUW {
+        rd: u8,
+        rs1: u8,
+        rs2: u8,
+    },
+}
+
+impl Instruction {
+    /// The registers this instruction names, as `[rd, rs1, rs2]`. Operands
+    /// the instruction does not have are reported as x0.
+    pub fn registers(&self) -> [u8; 3] {
+        use Instruction::*;
+        match self {
+            LUI { rd, .. }
+            | AUIPC { rd, .. }
+            | JAL { rd, .. }
+            | CSRRWI { rd, .. }
+            | CSRRSI { rd, .. }
+            | CSRRCI { rd, .. } => [*rd, 0, 0],
+            JALR { rd, rs1, .. }
+            | LB { rd, rs1, .. }
+            | LH { rd, rs1, .. }
+            | LW { rd, rs1, .. }
+            | LBU { rd, rs1, .. }
+            | LHU { rd, rs1, .. }
+            | ADDI { rd, rs1, .. }
+            | SLTI { rd, rs1, .. }
+            | SLTIU { rd, rs1, .. }
+            | XORI { rd, rs1, .. }
+            | ORI { rd, rs1, .. }
+            | ANDI { rd, rs1, .. }
+            | SLLI { rd, rs1, .. }
+            | SRLI { rd, rs1, .. }
+            | SRAI { rd, rs1, .. }
+            | LR_W { rd, rs1, .. }
+            | CSRRW { rd, rs1, .. }
+            | CSRRS { rd, rs1, .. }
+            | CSRRC { rd, rs1, .. }
+            | CLZ { rd, rs1, .. }
+            | CTZ { rd, rs1, .. }
+            | CPOP { rd, rs1, .. }
+            | SEXT_B { rd, rs1, .. }
+            | SEXT_H { rd, rs1, .. }
+            | ZEXT_H { rd, rs1, .. }
+            | ORC_B { rd, rs1, .. }
+            | REV8 { rd, rs1, .. }
+            | RORI { rd, rs1, .. }
+            | BCLRI { rd, rs1, .. }
+            | BEXTI { rd, rs1, .. }
+            | BINVI { rd, rs1, .. }
+            | BSETI { rd, rs1, .. }
+            | LWU { rd, rs1, .. }
+            | LD { rd, rs1, .. }
+            | ADDIW { rd, rs1, .. }
+            | SLLIW { rd, rs1, .. }
+            | SRLIW { rd, rs1, .. }
+            | SRAIW { rd, rs1, .. } => [*rd, *rs1, 0],
+            SB { rs1, rs2, .. }
+            | SH { rs1, rs2, .. }
+            | SW { rs1, rs2, .. }
+            | BEQ { rs1, rs2, .. }
+            | BNE { rs1, rs2, .. }
+            | BLT { rs1, rs2, .. }
+            | BGE { rs1, rs2, .. }
+            | BLTU { rs1, rs2, .. }
+            | BGEU { rs1, rs2, .. }
+            | SD { rs1, rs2, .. }
+            | SFENCE_VMA { rs1, rs2 } => [0, *rs1, *rs2],
+            ADD { rd, rs1, rs2, .. }
+            | SUB { rd, rs1, rs2, .. }
+            | SLL { rd, rs1, rs2, .. }
+            | SLT { rd, rs1, rs2, .. }
+            | SLTU { rd, rs1, rs2, .. }
+            | XOR { rd, rs1, rs2, .. }
+            | SRL { rd, rs1, rs2, .. }
+            | SRA { rd, rs1, rs2, .. }
+            | OR { rd, rs1, rs2, .. }
+            | AND { rd, rs1, rs2, .. }
+            | SC_W { rd, rs1, rs2, .. }
+            | AMOSWAP_W { rd, rs1, rs2, .. }
+            | AMOADD_W { rd, rs1, rs2, .. }
+            | AMOXOR_W { rd, rs1, rs2, .. }
+            | AMOAND_W { rd, rs1, rs2, .. }
+            | AMOOR_W { rd, rs1, rs2, .. }
+            | AMOMIN_W { rd, rs1, rs2, .. }
+            | AMOMAX_W { rd, rs1, rs2, .. }
+            | AMOMINU_W { rd, rs1, rs2, .. }
+            | AMOMAXU_W { rd, rs1, rs2, .. }
+            | MUL { rd, rs1, rs2, .. }
+            | MULH { rd, rs1, rs2, .. }
+            | MULHSU { rd, rs1, rs2, .. }
+            | MULHU { rd, rs1, rs2, .. }
+            | DIV { rd, rs1, rs2, .. }
+            | DIVU { rd, rs1, rs2, .. }
+            | REM { rd, rs1, rs2, .. }
+            | REMU { rd, rs1, rs2, .. }
+            | SH1ADD { rd, rs1, rs2, .. }
+            | SH2ADD { rd, rs1, rs2, .. }
+            | SH3ADD { rd, rs1, rs2, .. }
+            | ANDN { rd, rs1, rs2, .. }
+            | ORN { rd, rs1, rs2, .. }
+            | XNOR { rd, rs1, rs2, .. }
+            | MIN { rd, rs1, rs2, .. }
+            | MINU { rd, rs1, rs2, .. }
+            | MAX { rd, rs1, rs2, .. }
+            | MAXU { rd, rs1, rs2, .. }
+            | ROL { rd, rs1, rs2, .. }
+            | ROR { rd, rs1, rs2, .. }
+            | BCLR { rd, rs1, rs2, .. }
+            | BEXT { rd, rs1, rs2, .. }
+            | BINV { rd, rs1, rs2, .. }
+            | BSET { rd, rs1, rs2, .. }
+            | ADDW { rd, rs1, rs2, .. }
+            | SUBW { rd, rs1, rs2, .. }
+            | SLLW { rd, rs1, rs2, .. }
+            | SRLW { rd, rs1, rs2, .. }
+            | SRAW { rd, rs1, rs2, .. }
+            | MULW { rd, rs1, rs2, .. }
+            | DIVW { rd, rs1, rs2, .. }
+            | DIVUW { rd, rs1, rs2, .. }
+            | REMW { rd, rs1, rs2, .. }
+            | REMUW { rd, rs1, rs2, .. } => [*rd, *rs1, *rs2],
+            FENCE { .. } | FENCE_I | ECALL | EBREAK | MRET | WFI => [0, 0, 0],
+        }
+    }
 }
@@ -0,0 +1,224 @@
+use super::*;
+
+fn text(instr: Instruction) -> String {
+    instr.to_string()
+}
+
+#[test]
+fn test_display_base_instructions() {
+    use Instruction::*;
+    assert_eq!(
+        text(ADDI {
+            rd: 2,
+            rs1: 2,
+            imm: -16
+        }),
+        "addi sp, sp, -16"
+    );
+    assert_eq!(
+        text(LW {
+            rd: 10,
+            rs1: 2,
+            imm: 8
+        }),
+        "lw a0, 8(sp)"
+    );
+    assert_eq!(
+        text(SW {
+            rs1: 2,
+            rs2: 1,
+            imm: 12
+        }),
+        "sw ra, 12(sp)"
+    );
+    assert_eq!(
+        text(BLTU {
+            rs1: 10,
+            rs2: 11,
+            imm: -4
+        }),
+        "bltu a0, a1, -4"
+    );
+    assert_eq!(
+        text(LUI {
+            rd: 10,
+            imm: 0x1234_5000
+        }),
+        "lui a0, 0x12345"
+    );
+    assert_eq!(
+        text(SRAI {
+            rd: 15,
+            rs1: 15,
+            shamt: 3
+        }),
+        "srai a5, a5, 3"
+    );
+    assert_eq!(text(JAL { rd: 5, imm: 16 }), "jal t0, 16");
+    assert_eq!(text(ORC_B { rd: 10, rs1: 11 }), "orc.b a0, a1");
+    assert_eq!(
+        text(MULHU {
+            rd: 28,
+            rs1: 29,
+            rs2: 31
+        }),
+        "mulhu t3, t4, t6"
+    );
+    assert_eq!(
+        text(CSRRWI {
+            rd: 10,
+            uimm: 3,
+            csr: 0x300
+        }),
+        "csrrwi a0, mstatus, 3"
+    );
+    assert_eq!(
+        text(AMOADD_W {
+            rd: 10,
+            rs1: 12,
+            rs2: 11,
+            aq: true,
+            rl: true
+        }),
+        "amoadd.w.aqrl a0, a1, (a2)"
+    );
+    assert_eq!(
+        text(LR_W {
+            rd: 10,
+            rs1: 11,
+            aq: false,
+            rl: false
+        }),
+        "lr.w a0, (a1)"
+    );
+    assert_eq!(
+        text(FENCE {
+            fm: 0,
+            pred: 0x3,
+            succ: 0x1
+        }),
+        "fence rw, w"
+    );
+    assert_eq!(text(SFENCE_VMA { rs1: 10, rs2: 0 }), "sfence.vma a0");
+    assert_eq!(text(MRET), "mret");
+}
+
+#[test]
+fn test_display_pseudo_instructions() {
+    use Instruction::*;
+    assert_eq!(
+        text(ADDI {
+            rd: 0,
+            rs1: 0,
+            imm: 0
+        }),
+        "nop"
+    );
+    assert_eq!(
+        text(ADDI {
+            rd: 2,
+            rs1: 0,
+            imm: 5
+        }),
+        "li sp, 5"
+    );
+    assert_eq!(
+        text(ADDI {
+            rd: 10,
+            rs1: 11,
+            imm: 0
+        }),
+        "mv a0, a1"
+    );
+    assert_eq!(
+        text(XORI {
+            rd: 10,
+            rs1: 11,
+            imm: -1
+        }),
+        "not a0, a1"
+    );
+    assert_eq!(
+        text(SUB {
+            rd: 10,
+            rs1: 0,
+            rs2: 11
+        }),
+        "neg a0, a1"
+    );
+    assert_eq!(
+        text(JALR {
+            rd: 0,
+            rs1: 1,
+            imm: 0
+        }),
+        "ret"
+    );
+    assert_eq!(
+        text(JALR {
+            rd: 1,
+            rs1: 5,
+            imm: 0
+        }),
+        "jalr t0"
+    );
+    assert_eq!(text(JAL { rd: 0, imm: -8 }), "j -8");
+    assert_eq!(text(JAL { rd: 1, imm: 2048 }), "jal 2048");
+    assert_eq!(
+        text(BGE {
+            rs1: 0,
+            rs2: 10,
+            imm: 8
+        }),
+        "blez a0, 8"
+    );
+    assert_eq!(
+        text(CSRRS {
+            rd: 10,
+            rs1: 0,
+            csr: 0x342
+        }),
+        "csrr a0, mcause"
+    );
+    assert_eq!(
+        text(CSRRW {
+            rd: 0,
+            rs1: 6,
+            csr: 0x3B1
+        }),
+        "csrw pmpaddr1, t1"
+    );
+    assert_eq!(
+        text(FENCE {
+            fm: 0,
+            pred: 0xF,
+            succ: 0xF
+        }),
+        "fence"
+    );
+    assert_eq!(
+        text(FENCE {
+            fm: 0x8,
+            pred: 0x3,
+            succ: 0x3
+        }),
+        "fence.tso"
+    );
+}
+
+#[test]
+fn test_display_alternate_skips_aliases() {
+    let li = Instruction::ADDI {
+        rd: 2,
+        rs1: 0,
+        imm: 5,
+    };
+    assert_eq!(format!("{li:#}"), "addi sp, zero, 5");
+    let ret = Instruction::JALR {
+        rd: 0,
+        rs1: 1,
+        imm: 0,
+    };
+    assert_eq!(format!("{ret:#}"), "jalr zero, 0(ra)");
+    assert_eq!(abi_name(8), "s0");
+}
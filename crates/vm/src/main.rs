@@ -1,10 +1,84 @@
 pub mod compiler;
 pub mod constants;
 pub mod cpu;
+pub mod cpu64;
 pub mod decoder;
+pub mod disasm;
+pub mod encoder;
+pub mod isa;
 pub mod itxs;
 pub mod memory;
+pub mod syscall;
+
+use std::process::exit;
+
+use compiler::ElfLoader;
+use constants::{MAX_MEMORY_SIZE, REG_SP};
+use cpu::{CPU, StopReason, trap::TrapMode};
+use cpu64::CPU64;
+use memory::Memory;
 
 fn main() {
-    println!("Hello, world!");
+    let mut disassemble = false;
+    let mut guest_traps = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--disassemble" => disassemble = true,
+            "--guest-traps" => guest_traps = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let Some(path) = path else { usage() };
+
+    if disassemble {
+        match disasm::disassemble_elf(&path) {
+            Ok(lines) => lines.iter().for_each(|line| println!("{line}")),
+            Err(e) => {
+                eprintln!("failed to disassemble {path}: {e}");
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut memory = Memory::new(MAX_MEMORY_SIZE).expect("MAX_MEMORY_SIZE is a valid memory size");
+    let loaded = match ElfLoader::load_elf(&path, &mut memory) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("failed to load {path}: {e}");
+            exit(1);
+        }
+    };
+
+    let (reason, pc) = if loaded.is_64 {
+        let mut cpu = CPU64::default();
+        cpu.memory = memory;
+        cpu.set_pc(loaded.entry as u64);
+        cpu.write_reg(REG_SP, MAX_MEMORY_SIZE as u64);
+        (cpu.run(u64::MAX), cpu.pc())
+    } else {
+        let mut cpu = CPU::default();
+        cpu.memory = memory;
+        if guest_traps {
+            cpu.set_trap_mode(TrapMode::Guest);
+        }
+        cpu.set_pc(loaded.entry);
+        cpu.write_reg(REG_SP, MAX_MEMORY_SIZE as u32);
+        (cpu.run(u64::MAX), cpu.pc() as u64)
+    };
+
+    match reason {
+        StopReason::Halt(code) => exit(code),
+        reason => {
+            eprintln!("guest stopped at pc=0x{pc:08x}: {reason:?}");
+            exit(1);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: risc-v-vm-zk [--disassemble] [--guest-traps] <elf>");
+    exit(2);
 }
@@ -0,0 +1,110 @@
+/// Base address of the CLINT's register window. It sits far above the
+/// largest RAM the VM can be given, so it never shadows guest memory.
+pub const CLINT_BASE: u32 = 0xF000_0000;
+pub const CLINT_SIZE: u32 = 0x1_0000;
+pub const CLINT_MSIP: u32 = 0x0;
+pub const CLINT_MTIMECMP: u32 = 0x4000;
+pub const CLINT_MTIME: u32 = 0xBFF8;
+
+/// `mip`/`mie` bit for the machine software interrupt.
+pub const MIP_MSIP: u32 = 1 << 3;
+/// `mip`/`mie` bit for the machine timer interrupt.
+pub const MIP_MTIP: u32 = 1 << 7;
+
+/// A single-hart core-local interruptor: the `msip` doorbell and the
+/// `mtime`/`mtimecmp` timer, accessed as 32-bit words.
+///
+/// `mtime` advances once per step, so timer interrupts are deterministic.
+#[derive(Debug)]
+pub struct Clint {
+    msip: bool,
+    mtimecmp: u64,
+    mtime: u64,
+}
+
+impl Default for Clint {
+    fn default() -> Self {
+        Clint {
+            msip: false,
+            mtimecmp: u64::MAX,
+            mtime: 0,
+        }
+    }
+}
+
+impl Clint {
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    pub fn set_mtime(&mut self, mtime: u64) {
+        self.mtime = mtime;
+    }
+
+    pub fn mtimecmp(&self) -> u64 {
+        self.mtimecmp
+    }
+
+    pub fn set_mtimecmp(&mut self, mtimecmp: u64) {
+        self.mtimecmp = mtimecmp;
+    }
+
+    pub fn set_msip(&mut self, pending: bool) {
+        self.msip = pending;
+    }
+
+    pub fn tick(&mut self) {
+        self.mtime = self.mtime.wrapping_add(1);
+    }
+
+    /// The interrupts the CLINT is currently raising, as `mip` bits.
+    pub fn pending(&self) -> u32 {
+        let mut mip = 0;
+        if self.msip {
+            mip |= MIP_MSIP;
+        }
+        if self.mtime >= self.mtimecmp {
+            mip |= MIP_MTIP;
+        }
+        mip
+    }
+
+    /// Reads the register word at `offset` into the CLINT window.
+    pub fn read_word(&self, offset: u32) -> Option<u32> {
+        match offset {
+            CLINT_MSIP => Some(self.msip as u32),
+            CLINT_MTIMECMP => Some(self.mtimecmp as u32),
+            o if o == CLINT_MTIMECMP + 4 => Some((self.mtimecmp >> 32) as u32),
+            CLINT_MTIME => Some(self.mtime as u32),
+            o if o == CLINT_MTIME + 4 => Some((self.mtime >> 32) as u32),
+            _ => None,
+        }
+    }
+
+    /// Writes the register word at `offset`, returning `false` if there is
+    /// no register there.
+    pub fn write_word(&mut self, offset: u32, value: u32) -> bool {
+        match offset {
+            CLINT_MSIP => self.msip = value & 1 != 0,
+            CLINT_MTIMECMP => set_lo(&mut self.mtimecmp, value),
+            o if o == CLINT_MTIMECMP + 4 => set_hi(&mut self.mtimecmp, value),
+            CLINT_MTIME => set_lo(&mut self.mtime, value),
+            o if o == CLINT_MTIME + 4 => set_hi(&mut self.mtime, value),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// The offset of `addr` into the CLINT window, if it falls inside it.
+pub fn clint_offset(addr: u32) -> Option<u32> {
+    addr.checked_sub(CLINT_BASE).filter(|&o| o < CLINT_SIZE)
+}
+
+fn set_lo(reg: &mut u64, value: u32) {
+    *reg = (*reg & !0xFFFF_FFFF) | value as u64;
+}
+
+fn set_hi(reg: &mut u64, value: u32) {
+    *reg = (*reg & 0xFFFF_FFFF) | ((value as u64) << 32);
+}
@@ -1,13 +1,19 @@
+pub mod clint;
 pub mod errors;
+#[cfg(test)]
 pub mod tests;
 
 use crate::constants::{DEFAULT_MEMORY_SIZE, MAX_MEMORY_SIZE};
+use clint::{Clint, clint_offset};
 use errors::MemoryError;
 
+/// Guest RAM, plus the CLINT registers mapped at [`clint::CLINT_BASE`].
+/// The CLINT only answers aligned word (and doubleword) accesses.
 #[derive(Debug)]
 pub struct Memory {
     data: Vec<u8>,
     size: usize,
+    pub clint: Clint,
 }
 
 impl Memory {
@@ -21,6 +27,7 @@ impl Memory {
         Ok(Memory {
             data: vec![0; size],
             size,
+            clint: Clint::default(),
         })
     }
 
@@ -28,11 +35,75 @@ impl Memory {
         self.size
     }
 
+    /// Fetches the instruction at `addr`, which only needs to be 2-byte
+    /// aligned. A 16-bit compressed instruction is returned zero-extended;
+    /// the upper half is only read for 32-bit encodings.
     pub fn fetch(&self, addr: u32) -> Result<u32, MemoryError> {
-        self.load_word(addr)
+        let lo = self.load_half(addr)? as u32;
+        if lo & 0x3 != 0x3 {
+            return Ok(lo);
+        }
+        let hi = self.load_half(addr.wrapping_add(2))? as u32;
+        Ok(lo | (hi << 16))
+    }
+
+    pub fn load_double(&self, addr: u32) -> Result<u64, MemoryError> {
+        if clint_offset(addr).is_some() && addr.is_multiple_of(8) {
+            let lo = self.load_word(addr)? as u64;
+            let hi = self.load_word(addr + 4)? as u64;
+            return Ok(lo | (hi << 32));
+        }
+        let addr = addr as usize;
+        if addr + 8 > self.size {
+            return Err(MemoryError::OutOfBounds {
+                addr: addr as u32,
+                size: self.size,
+            });
+        }
+        if !addr.is_multiple_of(8) {
+            return Err(MemoryError::UnalignedAccess {
+                addr: addr as u32,
+                align: 8,
+            });
+        }
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&self.data[addr..addr + 8]);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn store_double(&mut self, addr: u32, value: u64) -> Result<(), MemoryError> {
+        if clint_offset(addr).is_some() && addr.is_multiple_of(8) {
+            self.store_word(addr, value as u32)?;
+            return self.store_word(addr + 4, (value >> 32) as u32);
+        }
+        let addr = addr as usize;
+        if addr + 8 > self.size {
+            return Err(MemoryError::OutOfBounds {
+                addr: addr as u32,
+                size: self.size,
+            });
+        }
+        if !addr.is_multiple_of(8) {
+            return Err(MemoryError::UnalignedAccess {
+                addr: addr as u32,
+                align: 8,
+            });
+        }
+        self.data[addr..addr + 8].copy_from_slice(&value.to_le_bytes());
+        Ok(())
     }
 
     pub fn load_word(&self, addr: u32) -> Result<u32, MemoryError> {
+        if let Some(offset) = clint_offset(addr) {
+            check_word_aligned(addr)?;
+            return self
+                .clint
+                .read_word(offset)
+                .ok_or(MemoryError::OutOfBounds {
+                    addr,
+                    size: self.size,
+                });
+        }
         let addr = addr as usize;
         if addr + 4 > self.size {
             return Err(MemoryError::OutOfBounds {
@@ -40,7 +111,7 @@ impl Memory {
                 size: self.size,
             });
         }
-        if addr % 4 != 0 {
+        if !addr.is_multiple_of(4) {
             return Err(MemoryError::UnalignedAccess {
                 addr: addr as u32,
                 align: 4,
@@ -56,6 +127,16 @@ impl Memory {
     }
 
     pub fn store_word(&mut self, addr: u32, value: u32) -> Result<(), MemoryError> {
+        if let Some(offset) = clint_offset(addr) {
+            check_word_aligned(addr)?;
+            if !self.clint.write_word(offset, value) {
+                return Err(MemoryError::OutOfBounds {
+                    addr,
+                    size: self.size,
+                });
+            }
+            return Ok(());
+        }
         let addr = addr as usize;
         if addr + 4 > self.size {
             return Err(MemoryError::OutOfBounds {
@@ -63,7 +144,7 @@ impl Memory {
                 size: self.size,
             });
         }
-        if addr % 4 != 0 {
+        if !addr.is_multiple_of(4) {
             return Err(MemoryError::UnalignedAccess {
                 addr: addr as u32,
                 align: 4,
@@ -74,6 +155,57 @@ impl Memory {
         Ok(())
     }
 
+    pub fn load_half(&self, addr: u32) -> Result<u16, MemoryError> {
+        let addr = addr as usize;
+        if addr + 2 > self.size {
+            return Err(MemoryError::OutOfBounds {
+                addr: addr as u32,
+                size: self.size,
+            });
+        }
+        if !addr.is_multiple_of(2) {
+            return Err(MemoryError::UnalignedAccess {
+                addr: addr as u32,
+                align: 2,
+            });
+        }
+        let bytes = &self.data[addr..addr + 2];
+        Ok((bytes[0] as u16) | ((bytes[1] as u16) << 8))
+    }
+
+    pub fn store_half(&mut self, addr: u32, value: u16) -> Result<(), MemoryError> {
+        let addr = addr as usize;
+        if addr + 2 > self.size {
+            return Err(MemoryError::OutOfBounds {
+                addr: addr as u32,
+                size: self.size,
+            });
+        }
+        if !addr.is_multiple_of(2) {
+            return Err(MemoryError::UnalignedAccess {
+                addr: addr as u32,
+                align: 2,
+            });
+        }
+        let bytes = value.to_le_bytes();
+        self.data[addr..addr + 2].copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Borrows the `len` bytes of RAM starting at `addr`. The CLINT is not
+    /// reachable this way.
+    pub fn load_bytes(&self, addr: u32, len: u32) -> Result<&[u8], MemoryError> {
+        let start = addr as usize;
+        let end = start.saturating_add(len as usize);
+        if end > self.size {
+            return Err(MemoryError::OutOfBounds {
+                addr,
+                size: self.size,
+            });
+        }
+        Ok(&self.data[start..end])
+    }
+
     pub fn load_byte(&self, addr: u32) -> Result<u8, MemoryError> {
         let addr = addr as usize;
         if addr >= self.size {
@@ -98,6 +230,13 @@ impl Memory {
     }
 }
 
+fn check_word_aligned(addr: u32) -> Result<(), MemoryError> {
+    if !addr.is_multiple_of(4) {
+        return Err(MemoryError::UnalignedAccess { addr, align: 4 });
+    }
+    Ok(())
+}
+
 impl Default for Memory {
     fn default() -> Self {
         Memory::new(DEFAULT_MEMORY_SIZE).unwrap()
@@ -7,6 +7,7 @@ const DEFAULT_UNALIGNED_ADDR: usize = 2;
 
 const DEFAULT_ADDR_TO_STORE: u32 = 4;
 const DEFAULT_BYTE_TO_STORE: u8 = 0xAB;
+const DEFAULT_HALF_TO_STORE: u16 = 0xBEEF;
 const DEFAULT_WORD_TO_STORE: u32 = 0x1234_5678;
 
 fn return_mem() -> Memory {
@@ -76,6 +77,40 @@ fn test_store_word_and_load_word() {
     assert_eq!(mem.load_word(4).unwrap(), DEFAULT_WORD_TO_STORE);
 }
 
+#[test]
+fn test_store_half_and_load_half() {
+    let mut mem = return_mem();
+    mem.store_half(DEFAULT_ADDR_TO_STORE, DEFAULT_HALF_TO_STORE)
+        .unwrap();
+    assert_eq!(mem.load_half(DEFAULT_ADDR_TO_STORE).unwrap(), 0xBEEF);
+    assert_eq!(mem.load_byte(DEFAULT_ADDR_TO_STORE).unwrap(), 0xEF);
+    assert_eq!(mem.load_byte(DEFAULT_ADDR_TO_STORE + 1).unwrap(), 0xBE);
+    assert_eq!(mem.load_byte(DEFAULT_ADDR_TO_STORE + 2).unwrap(), 0);
+}
+
+#[test]
+fn test_load_half_unaligned() {
+    let mem = return_mem();
+    let err = mem.load_half(3).unwrap_err();
+    assert_eq!(err, MemoryError::UnalignedAccess { addr: 3, align: 2 });
+    assert!(mem.load_half(DEFAULT_UNALIGNED_ADDR as u32).is_ok());
+}
+
+#[test]
+fn test_store_half_out_of_bounds() {
+    let mut mem = return_mem();
+    let err = mem
+        .store_half(DEFAULT_MEMORY_SIZE as u32, DEFAULT_HALF_TO_STORE)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        MemoryError::OutOfBounds {
+            addr: DEFAULT_MEMORY_SIZE as u32,
+            size: DEFAULT_MEMORY_SIZE
+        }
+    );
+}
+
 #[test]
 fn test_load_word_out_of_bounds() {
     let mem = return_mem();
@@ -88,3 +123,95 @@ fn test_load_word_out_of_bounds() {
         }
     );
 }
+
+#[test]
+fn test_load_bytes() {
+    let mut mem = return_mem();
+    mem.store_word(DEFAULT_ADDR_TO_STORE, DEFAULT_WORD_TO_STORE)
+        .unwrap();
+    assert_eq!(
+        mem.load_bytes(DEFAULT_ADDR_TO_STORE, 4).unwrap(),
+        [0x78, 0x56, 0x34, 0x12]
+    );
+    assert_eq!(mem.load_bytes(128, 0).unwrap(), []);
+    assert_eq!(
+        mem.load_bytes(120, 16),
+        Err(MemoryError::OutOfBounds {
+            addr: 120,
+            size: DEFAULT_MEMORY_SIZE
+        })
+    );
+}
+
+#[test]
+fn test_fetch_compressed_and_full() {
+    let mut mem = return_mem();
+    mem.store_word(0, 0x0050_0113).unwrap();
+    mem.store_half(4, 0x4532).unwrap();
+    mem.store_half(6, 0x0113).unwrap();
+    mem.store_half(8, 0x0050).unwrap();
+    mem.store_half(DEFAULT_MEMORY_SIZE as u32 - 2, 0x0001)
+        .unwrap();
+
+    assert_eq!(mem.fetch(0).unwrap(), 0x0050_0113);
+    assert_eq!(mem.fetch(4).unwrap(), 0x4532);
+    assert_eq!(mem.fetch(6).unwrap(), 0x0050_0113);
+    assert_eq!(mem.fetch(DEFAULT_MEMORY_SIZE as u32 - 2).unwrap(), 0x0001);
+    assert_eq!(
+        mem.fetch(1).unwrap_err(),
+        MemoryError::UnalignedAccess { addr: 1, align: 2 }
+    );
+}
+
+#[test]
+fn test_load_store_double() {
+    let mut mem = return_mem();
+    mem.store_double(8, 0x1122_3344_5566_7788).unwrap();
+    assert_eq!(mem.load_double(8).unwrap(), 0x1122_3344_5566_7788);
+    assert_eq!(mem.load_word(8).unwrap(), 0x5566_7788);
+    assert_eq!(mem.load_word(12).unwrap(), 0x1122_3344);
+    assert_eq!(
+        mem.load_double(4).unwrap_err(),
+        MemoryError::UnalignedAccess { addr: 4, align: 8 }
+    );
+    assert_eq!(
+        mem.store_double(DEFAULT_MEMORY_SIZE as u32 - 4, 0)
+            .unwrap_err(),
+        MemoryError::OutOfBounds {
+            addr: DEFAULT_MEMORY_SIZE as u32 - 4,
+            size: DEFAULT_MEMORY_SIZE
+        }
+    );
+}
+
+#[test]
+fn test_clint_registers() {
+    use crate::memory::clint::{CLINT_BASE, CLINT_MSIP, CLINT_MTIME, CLINT_MTIMECMP, MIP_MSIP};
+    let mut mem = return_mem();
+    assert_eq!(
+        mem.load_word(CLINT_BASE + CLINT_MTIMECMP).unwrap(),
+        u32::MAX
+    );
+
+    mem.store_word(CLINT_BASE + CLINT_MSIP, 1).unwrap();
+    assert_eq!(mem.clint.pending(), MIP_MSIP);
+    mem.store_double(CLINT_BASE + CLINT_MTIME, 0x1_0000_0002)
+        .unwrap();
+    assert_eq!(mem.clint.mtime(), 0x1_0000_0002);
+    assert_eq!(mem.load_word(CLINT_BASE + CLINT_MTIME + 4).unwrap(), 1);
+    assert_eq!(
+        mem.load_double(CLINT_BASE + CLINT_MTIME).unwrap(),
+        0x1_0000_0002
+    );
+
+    assert_eq!(
+        mem.load_word(CLINT_BASE + 2).unwrap_err(),
+        MemoryError::UnalignedAccess {
+            addr: CLINT_BASE + 2,
+            align: 4
+        }
+    );
+    // holes in the window and sub-word accesses are not backed by anything
+    assert!(mem.load_word(CLINT_BASE + 0x100).is_err());
+    assert!(mem.load_byte(CLINT_BASE).is_err());
+}
@@ -0,0 +1,232 @@
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    constants::{REG_A0, SYS_EXIT, SYS_WRITE},
+    cpu::{StopReason, errors::CPUError},
+    memory::Memory,
+};
+
+/// What a syscall handler gets to see of the machine: the register file and
+/// guest memory.
+///
+/// `regs` only holds the registers the ISA has (x0-x15 under RV32E); the
+/// others read as zero and ignore writes, like x0.
+pub struct SyscallContext<'a> {
+    regs: &'a mut [u32],
+    pub memory: &'a mut Memory,
+}
+
+impl<'a> SyscallContext<'a> {
+    pub fn new(regs: &'a mut [u32], memory: &'a mut Memory) -> Self {
+        SyscallContext { regs, memory }
+    }
+
+    pub fn read_reg(&self, idx: usize) -> u32 {
+        if idx == 0 {
+            0
+        } else {
+            self.regs.get(idx).copied().unwrap_or(0)
+        }
+    }
+
+    pub fn write_reg(&mut self, idx: usize, value: u32) {
+        if idx != 0
+            && let Some(reg) = self.regs.get_mut(idx)
+        {
+            *reg = value;
+        }
+    }
+
+    /// Returns syscall argument `n`, i.e. register a`n`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is not an argument register, i.e. not in `0..=6`.
+    pub fn arg(&self, n: usize) -> u32 {
+        assert!(n <= 6, "syscall argument a{n} does not exist");
+        self.read_reg(REG_A0 + n)
+    }
+
+    pub fn set_return(&mut self, value: u32) {
+        self.write_reg(REG_A0, value);
+    }
+}
+
+/// A host function reachable from the guest through `ecall`.
+///
+/// Returning `Some(reason)` stops the run loop once the `ecall` has retired.
+pub trait SyscallHandler {
+    fn handle(&mut self, ctx: &mut SyscallContext) -> Result<Option<StopReason>, CPUError>;
+}
+
+impl<F> SyscallHandler for F
+where
+    F: FnMut(&mut SyscallContext) -> Result<Option<StopReason>, CPUError>,
+{
+    fn handle(&mut self, ctx: &mut SyscallContext) -> Result<Option<StopReason>, CPUError> {
+        self(ctx)
+    }
+}
+
+/// `exit(code)`: halts the guest with the code in a0.
+pub struct Exit;
+
+impl SyscallHandler for Exit {
+    fn handle(&mut self, ctx: &mut SyscallContext) -> Result<Option<StopReason>, CPUError> {
+        Ok(Some(StopReason::Halt(ctx.arg(0) as i32)))
+    }
+}
+
+pub const STDOUT_FD: u32 = 1;
+pub const STDERR_FD: u32 = 2;
+
+const EIO: i32 = 5;
+const EBADF: i32 = 9;
+const EFAULT: i32 = 14;
+
+/// `write(fd, ptr, len)`: copies `len` guest bytes starting at `ptr` to the
+/// host sink registered for `fd` and returns `len` in a0. Unknown fds
+/// return `-EBADF`, buffers outside guest memory `-EFAULT` and host I/O
+/// failures `-EIO`.
+pub struct Write {
+    sinks: HashMap<u32, Box<dyn io::Write + Send>>,
+}
+
+impl Write {
+    /// A handler with no fds attached.
+    pub fn new() -> Self {
+        Write {
+            sinks: HashMap::new(),
+        }
+    }
+
+    /// A handler forwarding fd 1 and 2 to the host's stdout and stderr.
+    pub fn stdio() -> Self {
+        Write::new()
+            .with_sink(STDOUT_FD, io::stdout())
+            .with_sink(STDERR_FD, io::stderr())
+    }
+
+    pub fn with_sink<W>(mut self, fd: u32, sink: W) -> Self
+    where
+        W: io::Write + Send + 'static,
+    {
+        self.sinks.insert(fd, Box::new(sink));
+        self
+    }
+}
+
+impl Default for Write {
+    fn default() -> Self {
+        Write::new()
+    }
+}
+
+impl SyscallHandler for Write {
+    fn handle(&mut self, ctx: &mut SyscallContext) -> Result<Option<StopReason>, CPUError> {
+        let (fd, ptr, len) = (ctx.arg(0), ctx.arg(1), ctx.arg(2));
+        let Some(sink) = self.sinks.get_mut(&fd) else {
+            ctx.set_return(-EBADF as u32);
+            return Ok(None);
+        };
+        let Ok(bytes) = ctx.memory.load_bytes(ptr, len) else {
+            ctx.set_return(-EFAULT as u32);
+            return Ok(None);
+        };
+        match sink.write_all(bytes).and_then(|_| sink.flush()) {
+            Ok(()) => ctx.set_return(len),
+            Err(_) => ctx.set_return(-EIO as u32),
+        }
+        Ok(None)
+    }
+}
+
+/// An in-memory sink for [`Write`] that the host can read back after the
+/// guest has run.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        SharedBuffer::default()
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct SyscallRegistry {
+    handlers: HashMap<u32, Box<dyn SyscallHandler + Send>>,
+}
+
+impl SyscallRegistry {
+    /// A registry with no handlers at all, not even `exit`.
+    pub fn empty() -> Self {
+        SyscallRegistry {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers `handler` for syscall `num`, returning the handler it
+    /// replaced, if any.
+    pub fn register<H>(&mut self, num: u32, handler: H) -> Option<Box<dyn SyscallHandler + Send>>
+    where
+        H: SyscallHandler + Send + 'static,
+    {
+        self.handlers.insert(num, Box::new(handler))
+    }
+
+    pub fn unregister(&mut self, num: u32) -> Option<Box<dyn SyscallHandler + Send>> {
+        self.handlers.remove(&num)
+    }
+
+    pub fn contains(&self, num: u32) -> bool {
+        self.handlers.contains_key(&num)
+    }
+
+    pub fn get_mut(&mut self, num: u32) -> Option<&mut dyn SyscallHandler> {
+        self.handlers
+            .get_mut(&num)
+            .map(|h| h.as_mut() as &mut dyn SyscallHandler)
+    }
+
+    /// Runs the handler for syscall `num`, failing with
+    /// [`CPUError::UnknownSyscall`] if there is none.
+    pub fn dispatch(
+        &mut self,
+        num: u32,
+        ctx: &mut SyscallContext,
+    ) -> Result<Option<StopReason>, CPUError> {
+        self.get_mut(num)
+            .ok_or(CPUError::UnknownSyscall(num))?
+            .handle(ctx)
+    }
+}
+
+impl Default for SyscallRegistry {
+    fn default() -> Self {
+        let mut registry = SyscallRegistry::empty();
+        registry.register(SYS_EXIT, Exit);
+        registry.register(SYS_WRITE, Write::stdio());
+        registry
+    }
+}
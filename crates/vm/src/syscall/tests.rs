@@ -0,0 +1,115 @@
+use super::*;
+
+fn return_regs_and_mem() -> ([u32; 32], Memory) {
+    ([0; 32], Memory::default())
+}
+
+#[test]
+fn test_context_args_and_return() {
+    let (mut regs, mut mem) = return_regs_and_mem();
+    regs[REG_A0] = 7;
+    regs[REG_A0 + 2] = 9;
+    let mut ctx = SyscallContext::new(&mut regs, &mut mem);
+    assert_eq!(ctx.arg(0), 7);
+    assert_eq!(ctx.arg(2), 9);
+    ctx.set_return(42);
+    ctx.write_reg(0, 1);
+    assert_eq!(ctx.read_reg(0), 0);
+    assert_eq!(regs[REG_A0], 42);
+}
+
+#[test]
+#[should_panic(expected = "a7 does not exist")]
+fn test_context_arg_out_of_range() {
+    let (mut regs, mut mem) = return_regs_and_mem();
+    SyscallContext::new(&mut regs, &mut mem).arg(7);
+}
+
+#[test]
+fn test_registry_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<SyscallRegistry>();
+}
+
+#[test]
+fn test_default_registry_has_exit() {
+    let mut registry = SyscallRegistry::default();
+    assert!(registry.contains(SYS_EXIT));
+    assert!(registry.contains(SYS_WRITE));
+
+    let (mut regs, mut mem) = return_regs_and_mem();
+    regs[REG_A0] = 3;
+    let mut ctx = SyscallContext::new(&mut regs, &mut mem);
+    let stop = registry
+        .get_mut(SYS_EXIT)
+        .unwrap()
+        .handle(&mut ctx)
+        .unwrap();
+    assert_eq!(stop, Some(StopReason::Halt(3)));
+
+    assert!(!SyscallRegistry::empty().contains(SYS_EXIT));
+}
+
+#[test]
+fn test_register_and_unregister() {
+    let mut registry = SyscallRegistry::empty();
+    let prev = registry.register(1, |ctx: &mut SyscallContext| {
+        ctx.set_return(ctx.arg(0) + 1);
+        Ok(None)
+    });
+    assert!(prev.is_none());
+    assert!(registry.contains(1));
+
+    let (mut regs, mut mem) = return_regs_and_mem();
+    regs[REG_A0] = 1;
+    let mut ctx = SyscallContext::new(&mut regs, &mut mem);
+    assert_eq!(registry.get_mut(1).unwrap().handle(&mut ctx), Ok(None));
+    assert_eq!(regs[REG_A0], 2);
+
+    assert!(registry.register(1, Exit).is_some());
+    assert!(registry.unregister(1).is_some());
+    assert!(registry.get_mut(1).is_none());
+}
+
+#[test]
+fn test_write_to_buffer() {
+    let out = SharedBuffer::new();
+    let mut write = Write::new().with_sink(STDOUT_FD, out.clone());
+
+    let (mut regs, mut mem) = return_regs_and_mem();
+    for (i, b) in b"hello\n".iter().enumerate() {
+        mem.store_byte(16 + i as u32, *b).unwrap();
+    }
+    regs[REG_A0] = STDOUT_FD;
+    regs[REG_A0 + 1] = 16;
+    regs[REG_A0 + 2] = 6;
+    let mut ctx = SyscallContext::new(&mut regs, &mut mem);
+    assert_eq!(write.handle(&mut ctx), Ok(None));
+    assert_eq!(regs[REG_A0], 6);
+    assert_eq!(out.contents(), b"hello\n");
+}
+
+#[test]
+fn test_write_bad_fd_and_bad_pointer() {
+    let mut write = Write::new().with_sink(STDOUT_FD, SharedBuffer::new());
+
+    let (mut regs, mut mem) = return_regs_and_mem();
+    regs[REG_A0] = STDERR_FD;
+    let mut ctx = SyscallContext::new(&mut regs, &mut mem);
+    assert_eq!(write.handle(&mut ctx), Ok(None));
+    assert_eq!(regs[REG_A0] as i32, -EBADF);
+
+    regs[REG_A0] = STDOUT_FD;
+    regs[REG_A0 + 1] = 120;
+    regs[REG_A0 + 2] = 16;
+    let mut ctx = SyscallContext::new(&mut regs, &mut mem);
+    assert_eq!(write.handle(&mut ctx), Ok(None));
+    assert_eq!(regs[REG_A0] as i32, -EFAULT);
+
+    regs[REG_A0] = STDOUT_FD;
+    regs[REG_A0 + 1] = 16;
+    regs[REG_A0 + 2] = u32::MAX;
+    let mut ctx = SyscallContext::new(&mut regs, &mut mem);
+    assert_eq!(write.handle(&mut ctx), Ok(None));
+    assert_eq!(regs[REG_A0] as i32, -EFAULT);
+}